@@ -0,0 +1,57 @@
+use bitmachine::bitstring::{Bit, BitString};
+use bitmachine::property::bindings_from_source;
+use bitmachine::value::Value;
+use bitmachine::vm::VM;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const BITS: usize = 1_000_000;
+
+const INC: &str = "\
+inc x+0 = x+1
+inc x+1 = (inc x)+0
+inc .   = 1
+";
+
+/// `len` bits of a fixed pattern followed by `ones` ones.
+fn input(len: usize, ones: usize) -> BitString {
+    (0..len - ones)
+        .map(|i| if i % 3 == 0 { Bit::One } else { Bit::Zero })
+        .chain(std::iter::repeat_n(Bit::One, ones))
+        .collect()
+}
+
+fn bitstring(c: &mut Criterion) {
+    let s = input(BITS, 0);
+    c.bench_function("clone 10^6 bits", |b| b.iter(|| black_box(&s).clone()));
+    c.bench_function("slice 10^6 bits", |b| {
+        b.iter(|| black_box(&s).slice(1, BITS - 1))
+    });
+
+    let left = s.slice(0, BITS / 2);
+    let right = s.slice(BITS / 2, BITS);
+    let unaligned = s.slice(3, BITS / 2);
+    c.bench_function("concat 10^6 bits, aligned", |b| {
+        b.iter(|| black_box(&left).concat(black_box(&right)))
+    });
+    c.bench_function("concat 10^6 bits, unaligned", |b| {
+        b.iter(|| black_box(&right).concat(black_box(&unaligned)))
+    });
+}
+
+fn inc(c: &mut Criterion) {
+    let bindings = bindings_from_source(INC).unwrap();
+    for ones in [0, 1000] {
+        let argument = input(BITS, ones);
+        c.bench_function(&format!("inc on 10^6 bits ending in {} ones", ones), |b| {
+            b.iter(|| {
+                let mut vm = VM::new(bindings.clone());
+                vm.invoke_by_name("inc", vec![Value::from(argument.clone())])
+                    .unwrap();
+                vm.run(None).unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bitstring, inc);
+criterion_main!(benches);
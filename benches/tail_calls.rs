@@ -0,0 +1,42 @@
+use bitmachine::bitstring::{Bit, BitString};
+use bitmachine::property::bindings_from_source;
+use bitmachine::value::Value;
+use bitmachine::vm::VM;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Each function emits one bit per tail call, before or after the result
+/// of the call, so the time per input bit should not grow with the input.
+const EMITTERS: &str = "\
+ones x+?b = 1+(ones x)
+ones .    = .
+
+zeros x+?b = (zeros x)+0
+zeros .    = .
+";
+
+fn emit(c: &mut Criterion) {
+    let bindings = bindings_from_source(EMITTERS).unwrap();
+    for function in ["ones", "zeros"] {
+        let mut group = c.benchmark_group(format!("{} per emitted bit", function));
+        for bits in [1_000, 10_000, 100_000] {
+            let argument: BitString = std::iter::repeat_n(Bit::One, bits).collect();
+            group.throughput(Throughput::Elements(bits as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(bits),
+                &argument,
+                |b, argument| {
+                    b.iter(|| {
+                        let mut vm = VM::new(bindings.clone());
+                        vm.invoke_by_name(function, vec![Value::from(argument.clone())])
+                            .unwrap();
+                        vm.run(None).unwrap()
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, emit);
+criterion_main!(benches);
@@ -1,10 +1,66 @@
 use crate::bitstring::BitString;
-use crate::pattern::MultiPattern;
+use crate::pattern::{MultiPattern, Pattern};
 use std::collections::HashMap;
 
+/// Byte range in the source, end exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
 #[derive(Debug)]
 pub struct Program {
+    pub imports: Vec<Import>,
     pub function_map: FunctionMap,
+    pub tests: Vec<Test>,
+    /// Evaluation of the calls made by the tests.
+    pub evaluation: Evaluation,
+}
+
+impl Program {
+    /// Evaluates the calls of every function and test in the given way,
+    /// whatever their files ask for.
+    pub fn set_evaluation(&mut self, evaluation: Evaluation) {
+        self.evaluation = evaluation;
+        for func in self.function_map.values_mut() {
+            func.evaluation = evaluation;
+        }
+    }
+}
+
+/// When the arguments of a call are evaluated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Evaluation {
+    /// Before the call.
+    #[default]
+    Eager,
+    /// The first time their bits are needed, if ever. Set by `pragma lazy`.
+    Lazy,
+}
+
+/// `import "path" as namespace`. Functions of the imported file are
+/// accessible as `namespace.name`.
+#[derive(Debug)]
+pub struct Import {
+    pub path: String,
+    pub namespace: String,
+}
+
+/// `test "name" = actual == expected` or `assert actual == expected`.
+/// A test without `== expected` expects `1`.
+#[derive(Debug)]
+pub struct Test {
+    pub name: String,
+    pub line: usize,
+    pub actual: Expr,
+    pub expected: Expr,
 }
 
 pub type FunctionMap = HashMap<String, Function>;
@@ -13,18 +69,48 @@ pub type FunctionMap = HashMap<String, Function>;
 pub struct Function {
     pub name: String,
     pub variants: Vec<FunctionVariant>,
+    /// Set by `pragma memo`: calls are looked up in and recorded to the
+    /// memo table of the VM.
+    pub memoized: bool,
+    /// Evaluation of the calls the function makes.
+    pub evaluation: Evaluation,
+    /// Set by `@name = ...`: the name stands for the function itself, and
+    /// its variant without patterns is only called by `!name`.
+    pub function_value: bool,
 }
 
 #[derive(Debug)]
 pub struct FunctionVariant {
+    /// The whole definition.
+    pub span: Span,
+    /// Name, patterns and guard.
+    pub head: Span,
+    pub line: usize,
     pub patterns: MultiPattern,
+    pub guard: Option<Expr>,
     pub body: Expr,
 }
 
 #[derive(Debug)]
 pub enum Expr {
-    Variable { name: String, trampoline: bool },
+    /// `name`, or `@name` without `trampoline`. `!name` is a call without
+    /// arguments.
+    Variable {
+        name: String,
+        trampoline: bool,
+        span: Span,
+    },
     Literal(BitString),
-    Call { callee: Box<Expr>, args: Vec<Expr> },
-    Cat { children: Vec<Expr> },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Cat {
+        children: Vec<Expr>,
+    },
+    Let {
+        pattern: Pattern,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
 }
@@ -1,7 +1,7 @@
 use crate::value::Value;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Bindings(HashMap<String, Value>);
 
 impl Bindings {
@@ -22,14 +22,45 @@ impl Bindings {
     }
 
     pub fn union_with(mut self, other: Bindings) -> Self {
-        self.0.extend(other.into_map().into_iter());
+        self.0.extend(other.into_map());
         self
     }
 
+    /// Adds all bindings from `other`, failing if a name is bound on both
+    /// sides to different values.
+    pub fn merge(mut self, other: Bindings) -> Option<Self> {
+        for (name, value) in other.into_map() {
+            if !self.add_consistent(name, value) {
+                return None;
+            }
+        }
+        Some(self)
+    }
+
+    /// Binds `name` unless it is already bound to a different value.
+    /// Returns whether the bindings are still consistent.
+    pub fn add_consistent(&mut self, name: String, value: Value) -> bool {
+        match self.0.get(&name) {
+            Some(existing) => *existing == value,
+            None => {
+                self.0.insert(name, value);
+                true
+            }
+        }
+    }
+
     pub fn add(&mut self, name: String, value: Value) {
         self.0.insert(name, value);
     }
 
+    pub fn replace(&mut self, name: String, value: Value) -> Option<Value> {
+        self.0.insert(name, value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.0.remove(name)
+    }
+
     pub fn get_value(&self, name: &str) -> Option<&Value> {
         self.0.get(name)
     }
@@ -1,41 +1,356 @@
 use itertools::Itertools;
-use std::iter::{self, FromIterator};
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::iter::{FromIterator, FusedIterator};
+use std::ops::{BitAnd, BitOr, BitXor, Index, Not, Shl, Shr};
 use std::str::FromStr;
+use std::sync::Arc;
 
-#[derive(Clone, Eq, PartialEq)]
+/// A string of bits sharing its storage with the strings it was sliced
+/// from or cloned from, so that both operations take constant time.
+/// Appending to a string that is the only user of its storage extends the
+/// storage in place.
+#[derive(Clone)]
 pub struct BitString {
-    bytes: Vec<u8>,
+    /// Bits packed most significant first. Only bits
+    /// `offset..offset + length` belong to the string.
+    bytes: Arc<Vec<u8>>,
+    offset: usize,
     length: usize,
 }
 
 impl std::fmt::Debug for BitString {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "{}", "BitString { ")?;
+        write!(fmt, "BitString {{ ")?;
         for bit in self.iter() {
             write!(fmt, "{}", bit)?;
         }
-        write!(fmt, "{}", " }")
+        write!(fmt, " }}")
+    }
+}
+
+impl PartialEq for BitString {
+    fn eq(&self, other: &BitString) -> bool {
+        self.length == other.length && self.range_eq(0, other, 0, self.length)
+    }
+}
+
+impl Eq for BitString {}
+
+impl Hash for BitString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.length.hash(state);
+        for index in (0..self.length).step_by(64) {
+            self.word_at(index, 64).hash(state);
+        }
+    }
+}
+
+/// Bit strings are ordered lexicographically, `0` before `1`, and a string
+/// comes before the longer strings it is a prefix of.
+impl Ord for BitString {
+    fn cmp(&self, other: &BitString) -> Ordering {
+        let common = self.length.min(other.length);
+        (0..common)
+            .step_by(64)
+            .map(|index| {
+                let count = (common - index).min(64);
+                self.word_at(index, count).cmp(&other.word_at(index, count))
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.length.cmp(&other.length))
+    }
+}
+
+impl PartialOrd for BitString {
+    fn partial_cmp(&self, other: &BitString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Default for BitString {
+    fn default() -> BitString {
+        BitString::empty()
     }
 }
 
 impl FromIterator<Bit> for BitString {
     fn from_iter<I: IntoIterator<Item = Bit>>(iter: I) -> BitString {
-        let iter = iter.into_iter();
-
-        let mut length = 0;
-        let bytes = iter
-            .chunks(8)
-            .into_iter()
-            .map(|chunk| {
-                chunk.fold(0, |num, bit| {
-                    let shift_magnitude = 7 - length % 8;
-                    length += 1;
-                    num | (bit.as_number() << shift_magnitude)
-                })
-            })
-            .collect();
+        let mut result = BitString::empty();
+        result.extend(iter);
+        result
+    }
+}
+
+impl Extend<Bit> for BitString {
+    fn extend<I: IntoIterator<Item = Bit>>(&mut self, iter: I) {
+        let mut word = 0;
+        let mut count = 0;
+        for bit in iter {
+            word = (word << 1) | bit.as_number() as u64;
+            count += 1;
+            if count == 64 {
+                self.push_u64(word, 64);
+                count = 0;
+            }
+        }
+        self.push_u64(word, count);
+    }
+}
+
+impl Extend<BitString> for BitString {
+    fn extend<I: IntoIterator<Item = BitString>>(&mut self, iter: I) {
+        for other in iter {
+            self.append(&other);
+        }
+    }
+}
+
+impl IntoIterator for BitString {
+    type Item = Bit;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter {
+            front: 0,
+            back: self.length,
+            word: 0,
+            cached: 0,
+            bits: self,
+        }
+    }
+}
+
+impl IntoIterator for &BitString {
+    type Item = Bit;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        self.clone().into_iter()
+    }
+}
+
+/// Iterator over the bits of a string, sharing its storage. Going forward
+/// reads a word at a time.
+#[derive(Debug, Clone)]
+pub struct IntoIter {
+    bits: BitString,
+    front: usize,
+    back: usize,
+    /// The bits from `front` on, most significant first.
+    word: u64,
+    /// How many bits of `word` are left.
+    cached: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = Bit;
+
+    fn next(&mut self) -> Option<Bit> {
+        if self.front == self.back {
+            return None;
+        }
+        if self.cached == 0 {
+            self.word = self.bits.word_at(self.front, 64);
+            self.cached = 64;
+        }
+        let bit = Bit::from_number((self.word >> 63) as u8).unwrap();
+        self.word <<= 1;
+        self.cached -= 1;
+        self.front += 1;
+        Some(bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
 
-        BitString { bytes, length }
+impl DoubleEndedIterator for IntoIter {
+    fn next_back(&mut self) -> Option<Bit> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.bits.bit_at(self.back)
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl FusedIterator for IntoIter {}
+
+/// Panics if `index` is out of range.
+impl Index<usize> for BitString {
+    type Output = Bit;
+
+    fn index(&self, index: usize) -> &Bit {
+        match self.bit_at(index) {
+            Some(Bit::Zero) => &Bit::Zero,
+            Some(Bit::One) => &Bit::One,
+            None => panic!(
+                "Bit index {} out of range for a string of {} bits",
+                index, self.length
+            ),
+        }
+    }
+}
+
+/// The bits, or `.` for the empty string as in source. The alternate form
+/// `{:#}` separates every 8 bits with `_`.
+impl std::fmt::Display for BitString {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_empty() {
+            return fmt.pad(".");
+        }
+        let mut text = String::with_capacity(self.length + self.length / 8);
+        for (i, bit) in self.iter().enumerate() {
+            if fmt.alternate() && i > 0 && i.is_multiple_of(8) {
+                text.push('_');
+            }
+            text.push(if bit == Bit::One { '1' } else { '0' });
+        }
+        fmt.pad(&text)
+    }
+}
+
+/// The bits, with a `0b` prefix in the alternate form `{:#b}`.
+impl std::fmt::Binary for BitString {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let digits: String = self.iter().map(|bit| bit.to_string()).collect();
+        fmt.pad_integral(true, "0b", &digits)
+    }
+}
+
+/// The number the bits stand for, with a digit for every 4 bits and a `0x`
+/// prefix in the alternate form `{:#x}`.
+impl std::fmt::LowerHex for BitString {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.pad_integral(true, "0x", &self.hex_digits(false))
+    }
+}
+
+impl std::fmt::UpperHex for BitString {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.pad_integral(true, "0x", &self.hex_digits(true))
+    }
+}
+
+macro_rules! impl_bitwise {
+    ($($trait:ident, $method:ident, $checked:ident, $op:tt, $name:expr);*) => {
+        $(
+            impl BitString {
+                #[doc = concat!("Bitwise ", $name, " of strings of the same length, or `None` if")]
+                /// their lengths differ.
+                pub fn $checked(&self, other: &BitString) -> Option<BitString> {
+                    self.zip_words(other, |a, b| a $op b)
+                }
+            }
+
+            #[doc = concat!("Bitwise ", $name, ".")]
+            ///
+            /// # Panics
+            ///
+            #[doc = concat!(
+                "Panics if the strings have different lengths. `BitString::",
+                stringify!($checked),
+                "` returns `None` for them instead."
+            )]
+            impl $trait<&BitString> for &BitString {
+                type Output = BitString;
+
+                fn $method(self, other: &BitString) -> BitString {
+                    self.$checked(other).unwrap_or_else(|| {
+                        panic!(
+                            "Cannot {} strings of {} and {} bits",
+                            $name,
+                            self.length,
+                            other.length
+                        )
+                    })
+                }
+            }
+
+            /// Panics like the operator on references.
+            impl $trait for BitString {
+                type Output = BitString;
+
+                fn $method(self, other: BitString) -> BitString {
+                    (&self).$method(&other)
+                }
+            }
+        )*
+    };
+}
+
+impl_bitwise!(
+    BitAnd, bitand, checked_and, &, "and";
+    BitOr, bitor, checked_or, |, "or";
+    BitXor, bitxor, checked_xor, ^, "xor"
+);
+
+impl Not for &BitString {
+    type Output = BitString;
+
+    fn not(self) -> BitString {
+        let mut result = BitString::with_capacity(self.length);
+        for index in (0..self.length).step_by(64) {
+            let count = (self.length - index).min(64);
+            result.push_u64(!self.word_at(index, count) >> (64 - count), count);
+        }
+        result
+    }
+}
+
+impl Not for BitString {
+    type Output = BitString;
+
+    fn not(self) -> BitString {
+        !&self
+    }
+}
+
+/// Shifts towards the start, keeping the length: the first `count` bits
+/// are dropped and as many zeros are added at the end.
+impl Shl<usize> for &BitString {
+    type Output = BitString;
+
+    fn shl(self, count: usize) -> BitString {
+        let count = count.min(self.length);
+        let mut result = self.slice(count, self.length);
+        result.append(&BitString::zeros(count));
+        result
+    }
+}
+
+impl Shl<usize> for BitString {
+    type Output = BitString;
+
+    fn shl(self, count: usize) -> BitString {
+        &self << count
+    }
+}
+
+/// Shifts towards the end, keeping the length: the last `count` bits are
+/// dropped and as many zeros are added at the start.
+impl Shr<usize> for &BitString {
+    type Output = BitString;
+
+    fn shr(self, count: usize) -> BitString {
+        let kept = self.length.saturating_sub(count);
+        let mut result = BitString::zeros(count.min(self.length));
+        result.append(&self.slice(0, kept));
+        result
+    }
+}
+
+impl Shr<usize> for BitString {
+    type Output = BitString;
+
+    fn shr(self, count: usize) -> BitString {
+        &self >> count
     }
 }
 
@@ -44,10 +359,9 @@ impl BitString {
         if index >= self.length {
             None
         } else {
-            let byte_index = index / 8;
-            let byte = self.bytes[byte_index];
-            let bit_subindex = index % 8;
-            Some(Bit::from_number_indexed(byte, bit_subindex as u8))
+            let index = self.offset + index;
+            let byte = self.bytes[index / 8];
+            Some(Bit::from_number_indexed(byte, (index % 8) as u8))
         }
     }
 
@@ -55,62 +369,274 @@ impl BitString {
         self.length
     }
 
-    pub fn bytes(&self) -> &Vec<u8> {
-        &self.bytes
+    /// The bits packed into bytes, most significant first, with the last
+    /// byte padded with zeros.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = (0..self.length)
+            .step_by(64)
+            .flat_map(|index| self.word_at(index, 64).to_be_bytes())
+            .collect();
+        bytes.truncate(self.length.div_ceil(8));
+        bytes
     }
 
-    pub fn bytes_mut(&mut self) -> &mut Vec<u8> {
-        &mut self.bytes
+    /// Every bit of `bytes`, most significant bit of each byte first.
+    pub fn from_bytes(bytes: &[u8]) -> BitString {
+        BitString {
+            bytes: Arc::new(bytes.to_vec()),
+            offset: 0,
+            length: bytes.len() * 8,
+        }
+    }
+
+    /// Bits from `start` (inclusive) to `end` (exclusive), sharing the
+    /// storage of `self`. `end` is clamped to the length of the string.
+    pub fn slice(&self, start: usize, end: usize) -> BitString {
+        let end = end.min(self.length);
+        assert!(start <= end, "Invalid bit string slice {}..{}", start, end);
+        BitString {
+            bytes: Arc::clone(&self.bytes),
+            offset: self.offset + start,
+            length: end - start,
+        }
     }
 
     pub fn concat(&self, other: &BitString) -> BitString {
-        self.iter().chain(other.iter()).collect()
+        Self::concat_all([self, other])
+    }
+
+    /// Concatenation of `parts`, copied word by word into a single buffer.
+    /// A single nonempty part is shared rather than copied.
+    pub fn concat_all<'a>(parts: impl IntoIterator<Item = &'a BitString>) -> BitString {
+        let parts: Vec<&BitString> = parts.into_iter().filter(|x| !x.is_empty()).collect();
+        match parts.as_slice() {
+            [] => BitString::empty(),
+            [part] => (*part).clone(),
+            _ => {
+                let length = parts.iter().map(|x| x.len()).sum::<usize>();
+                let mut result = BitString::with_capacity(length);
+                for part in parts {
+                    result.append(part);
+                }
+                result
+            }
+        }
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Bit> + 'a {
-        let len = self.len();
-        Self::iter_adapter(self.bytes.iter().copied(), len)
+    /// Adds the bits of `other` at the end, copying whole bytes when both
+    /// strings are aligned the same way and shifted words otherwise.
+    pub fn append(&mut self, other: &BitString) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other.clone();
+            return;
+        }
+
+        self.make_writable(other.length);
+        let end = self.offset + self.length;
+        if end.is_multiple_of(8) && other.offset.is_multiple_of(8) {
+            let first = other.offset / 8;
+            let whole_bytes = other.length / 8;
+            Arc::get_mut(&mut self.bytes)
+                .unwrap()
+                .extend_from_slice(&other.bytes[first..first + whole_bytes]);
+            self.length += whole_bytes * 8;
+            let rest = other.length % 8;
+            self.push_word(other.word_at(other.length - rest, rest), rest);
+        } else {
+            for index in (0..other.length).step_by(64) {
+                let count = (other.length - index).min(64);
+                self.push_word(other.word_at(index, count), count);
+            }
+        }
     }
 
-    // TODO: implement this as part of the IntoIterator trait.
-    // This is not yet done, because it doesn't seem like I can
-    // write `-> impl Iterator<...>` as the return type, and
-    // the real type of the iterator returned is so complex it's
-    // better left unnamed.
-    pub fn into_iter(self) -> impl Iterator<Item = Bit> {
-        let len = self.len();
-        Self::iter_adapter(self.bytes.into_iter(), len)
+    /// Adds the bits of `other` at the start. This copies `self`, unless
+    /// `other` is the only user of its storage and can be appended to.
+    pub fn prepend(&mut self, other: &BitString) {
+        let mut result = other.clone();
+        result.append(self);
+        *self = result;
     }
 
-    fn iter_adapter<'a, Iter>(byte_iter: Iter, len: usize) -> impl Iterator<Item = Bit> + 'a
-    where
-        Iter: Iterator<Item = u8> + 'a,
-    {
-        byte_iter.map(iter_bits_in_byte).flatten().take(len)
+    /// Adds the `count` least significant bits of `value` at the end, most
+    /// significant first.
+    pub fn push_u64(&mut self, value: u64, count: usize) {
+        assert!(count <= 64, "Cannot push {} bits of a u64", count);
+        if count > 0 {
+            self.make_writable(count);
+            self.push_word(value << (64 - count), count);
+        }
+    }
+
+    /// The `count` bits (at most 64) starting at `start` as the least
+    /// significant bits of a number.
+    pub fn get_u64(&self, start: usize, count: usize) -> u64 {
+        assert!(count <= 64, "Cannot get {} bits as a u64", count);
+        assert!(start + count <= self.length, "Bits out of range");
+        match count {
+            0 => 0,
+            _ => self.word_at(start, count) >> (64 - count),
+        }
+    }
+
+    /// Whether the `len` bits of `self` starting at `start` are the same as
+    /// those of `other` starting at `other_start`, compared a word at a time.
+    pub fn range_eq(
+        &self,
+        start: usize,
+        other: &BitString,
+        other_start: usize,
+        len: usize,
+    ) -> bool {
+        assert!(start + len <= self.length && other_start + len <= other.length);
+        (0..len).step_by(64).all(|i| {
+            let count = (len - i).min(64);
+            self.word_at(start + i, count) == other.word_at(other_start + i, count)
+        })
+    }
+
+    pub fn iter(&self) -> IntoIter {
+        self.into_iter()
+    }
+
+    /// Bits `index..index + count` of the string (`count` at most 64) in
+    /// the most significant bits of the result. The other bits are zero,
+    /// and so are those past the end of the string.
+    fn word_at(&self, index: usize, count: usize) -> u64 {
+        let count = count.min(self.length.saturating_sub(index));
+        if count == 0 {
+            return 0;
+        }
+        let start = self.offset + index;
+        let first = start / 8;
+        let (high, next) = match self.bytes.get(first..first + 9) {
+            Some(bytes) => (u64::from_be_bytes(bytes[..8].try_into().unwrap()), bytes[8]),
+            None => {
+                let mut buffer = [0; 9];
+                let available = self.bytes.len() - first;
+                buffer[..available].copy_from_slice(&self.bytes[first..]);
+                (
+                    u64::from_be_bytes(buffer[..8].try_into().unwrap()),
+                    buffer[8],
+                )
+            }
+        };
+        let shift = start % 8;
+        let word = if shift == 0 {
+            high
+        } else {
+            (high << shift) | (next >> (8 - shift)) as u64
+        };
+        word & (u64::MAX << (64 - count))
+    }
+
+    /// Makes the storage unique and ending with the string, with room for
+    /// `additional` more bits, copying it if needed.
+    fn make_writable(&mut self, additional: usize) {
+        let end = self.offset + self.length;
+        let in_place = Arc::get_mut(&mut self.bytes).is_some_and(|x| x.len() == end.div_ceil(8));
+        if !in_place {
+            let mut bytes = Vec::with_capacity((self.length + additional).div_ceil(8));
+            let first = self.offset / 8;
+            bytes.extend_from_slice(&self.bytes[first..end.div_ceil(8)]);
+            self.bytes = Arc::new(bytes);
+            self.offset %= 8;
+        }
+
+        // Bits past the end may be left over from a longer string.
+        let end = self.offset + self.length;
+        let bytes = Arc::get_mut(&mut self.bytes).unwrap();
+        bytes.reserve(additional.div_ceil(8));
+        if !end.is_multiple_of(8) {
+            *bytes.last_mut().unwrap() &= 0xff << (8 - end % 8);
+        }
+    }
+
+    /// Appends the `count` most significant bits of `word`, the others
+    /// being zero, to a string made writable beforehand.
+    fn push_word(&mut self, mut word: u64, mut count: usize) {
+        debug_assert!(count == 64 || word << count == 0);
+        let bytes = Arc::get_mut(&mut self.bytes).unwrap();
+        let used = (self.offset + self.length) % 8;
+        if used != 0 && count > 0 {
+            let taken = (8 - used).min(count);
+            *bytes.last_mut().unwrap() |= (word >> (56 + used)) as u8;
+            word <<= taken;
+            count -= taken;
+            self.length += taken;
+        }
+        bytes.extend_from_slice(&word.to_be_bytes()[..count.div_ceil(8)]);
+        self.length += count;
+    }
+
+    /// Hexadecimal digits of the number the bits stand for, one per 4 bits
+    /// counted from the end, so that leading zeros are kept.
+    fn hex_digits(&self, upper: bool) -> String {
+        let mut digits = String::with_capacity(self.length.div_ceil(4));
+        let mut start = 0;
+        let mut count = self.length % 4;
+        if count == 0 {
+            count = 4;
+        }
+        while start < self.length {
+            let digit = std::char::from_digit(self.get_u64(start, count) as u32, 16).unwrap();
+            digits.push(if upper {
+                digit.to_ascii_uppercase()
+            } else {
+                digit
+            });
+            start += count;
+            count = 4;
+        }
+        digits
+    }
+
+    /// `op` applied to the words of `self` and `other`, or `None` if their
+    /// lengths differ, like the operands of `band` in the prelude.
+    fn zip_words(&self, other: &BitString, op: fn(u64, u64) -> u64) -> Option<BitString> {
+        if self.length != other.length {
+            return None;
+        }
+        let mut result = BitString::with_capacity(self.length);
+        for index in (0..self.length).step_by(64) {
+            let count = (self.length - index).min(64);
+            let word = op(self.word_at(index, count), other.word_at(index, count));
+            result.push_u64(word >> (64 - count), count);
+        }
+        Some(result)
+    }
+
+    fn zeros(count: usize) -> BitString {
+        let mut result = BitString::with_capacity(count);
+        for index in (0..count).step_by(64) {
+            result.push_u64(0, (count - index).min(64));
+        }
+        result
+    }
+
+    pub fn with_capacity(bits: usize) -> BitString {
+        BitString {
+            bytes: Arc::new(Vec::with_capacity(bits.div_ceil(8))),
+            offset: 0,
+            length: 0,
+        }
     }
 
     pub fn empty() -> BitString {
-        iter::empty().collect()
+        BitString::with_capacity(0)
     }
 
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
-    pub fn as_usize(&self) -> usize {
-        self.iter()
-            .fold(0, |a, b| (a << 1) | (b.as_number() as usize))
-    }
-
     pub fn from_u64(num: u64) -> BitString {
-        (0..64)
-            .map(|i| {
-                let i = i as u64;
-                let shift_magnitude = 63 - i;
-                let bit_num = (num & (1 << shift_magnitude)) >> shift_magnitude;
-                Bit::from_number(bit_num as u8).unwrap()
-            })
-            .collect()
+        let mut result = BitString::with_capacity(64);
+        result.push_u64(num, 64);
+        result
     }
 }
 
@@ -132,7 +658,8 @@ impl FromStr for BitString {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Bit {
     Zero,
     One,
@@ -174,10 +701,6 @@ impl Bit {
     }
 }
 
-fn iter_bits_in_byte(byte: u8) -> impl Iterator<Item = Bit> {
-    (0..8).map(move |index| Bit::from_number_indexed(byte, index))
-}
-
 #[derive(Debug)]
 pub struct BitFromStringError;
 
@@ -198,3 +721,111 @@ impl std::fmt::Display for Bit {
         write!(fmt, "{}", self.as_number())
     }
 }
+
+/// Bit strings are written as their text, e.g. `"1011"` or `"."`, in
+/// human-readable formats, and as their length followed by their bytes in
+/// the others.
+#[cfg(feature = "serde")]
+mod serialization {
+    use super::BitString;
+    use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeTuple, Serializer};
+    use std::fmt;
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    struct ByteBuf(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for ByteBuf {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteBuf, D::Error> {
+            deserializer.deserialize_byte_buf(ByteBufVisitor)
+        }
+    }
+
+    struct ByteBufVisitor;
+
+    impl<'de> Visitor<'de> for ByteBufVisitor {
+        type Value = ByteBuf;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt.write_str("bytes")
+        }
+
+        fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ByteBuf, E> {
+            Ok(ByteBuf(bytes.to_vec()))
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<ByteBuf, E> {
+            Ok(ByteBuf(bytes))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+            let mut bytes = Vec::new();
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(ByteBuf(bytes))
+        }
+    }
+
+    impl Serialize for BitString {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                let mut tuple = serializer.serialize_tuple(2)?;
+                tuple.serialize_element(&self.len())?;
+                tuple.serialize_element(&Bytes(&self.to_bytes()))?;
+                tuple.end()
+            }
+        }
+    }
+
+    struct BitStringVisitor;
+
+    impl<'de> Visitor<'de> for BitStringVisitor {
+        type Value = BitString;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt.write_str("a bit string")
+        }
+
+        fn visit_str<E: de::Error>(self, text: &str) -> Result<BitString, E> {
+            text.parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(text), &self))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BitString, A::Error> {
+            let length: usize = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let ByteBuf(bytes) = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            if bytes.len() != length.div_ceil(8) {
+                return Err(de::Error::custom(format!(
+                    "{} bytes cannot hold exactly {} bits",
+                    bytes.len(),
+                    length
+                )));
+            }
+            Ok(BitString::from_bytes(&bytes).slice(0, length))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for BitString {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BitString, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(BitStringVisitor)
+            } else {
+                deserializer.deserialize_tuple(2, BitStringVisitor)
+            }
+        }
+    }
+}
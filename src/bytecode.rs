@@ -1,7 +1,9 @@
 use crate::bitstring::BitString;
+use crate::pattern::Pattern;
 use std::iter::Iterator;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bytecode {
     instructions: Vec<Instruction>,
 }
@@ -21,13 +23,34 @@ impl Bytecode {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     LoadConst(BitString),
-    LoadVar { name: String },
+    LoadVar {
+        name: String,
+    },
+    /// Calls the value on top of the stack without arguments if it is a
+    /// coded function that is trampoline-callable, then runs again on the
+    /// value it returns, until the value is anything else. Emitted after
+    /// every name but `@name`.
     Trampoline,
     Call(usize),
     Cat(usize),
-    Tail { prepend: usize, append: usize },
+    Tail {
+        prepend: usize,
+        append: usize,
+    },
+    Bind(Pattern),
+    Unbind,
+    /// Pushes a thunk evaluating the code when its value is needed, or the
+    /// value of the variable if the code only refers to one.
+    Delay(Bytecode),
+}
+
+impl Instruction {
+    pub fn is_tail(&self) -> bool {
+        matches!(self, Instruction::Tail { .. })
+    }
 }
 
 pub trait Pretty {
@@ -45,6 +68,12 @@ impl Pretty for Instruction {
             Instruction::Tail { prepend, append } => {
                 format!("tail [pre = {}], [app = {}]", prepend, append)
             }
+            Instruction::Bind(pattern) => format!("bind {:?}", pattern),
+            Instruction::Unbind => String::from("unbind"),
+            Instruction::Delay(bytecode) => {
+                let code: Vec<_> = bytecode.iter().map(Pretty::pretty).collect();
+                format!("delay {{{}}}", code.join("; "))
+            }
         }
     }
 }
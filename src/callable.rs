@@ -2,11 +2,21 @@ use crate::coded_function::CodedFunction;
 use crate::native_function::NativeFunction;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Callable {
     Coded(CodedFunction),
     Native(NativeFunction),
 }
 
+impl Callable {
+    pub fn name(&self) -> &str {
+        match self {
+            Callable::Coded(func) => &func.name,
+            Callable::Native(func) => &func.name,
+        }
+    }
+}
+
 impl From<CodedFunction> for Callable {
     fn from(func: CodedFunction) -> Callable {
         Callable::Coded(func)
@@ -0,0 +1,151 @@
+//! Static checks on a parsed program.
+
+use crate::ast::{Expr, FunctionVariant, Program, Span};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Use of a name in an expression.
+pub struct Reference<'a> {
+    pub name: &'a str,
+    pub span: Span,
+    /// Whether the name is bound by a pattern or a `let` around it.
+    pub local: bool,
+    /// Variant the expression belongs to, `None` for tests.
+    pub variant: Option<&'a FunctionVariant>,
+}
+
+/// Reports uses of names that are neither local nor in `globals` nor
+/// qualified with an imported namespace, functions whose variants with
+/// patterns take different numbers of arguments, and functions that also
+/// have a variant without patterns, which naming them calls, unless they are
+/// defined as function values.
+pub fn check(program: &Program, globals: &HashSet<String>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let namespaces: Vec<String> = program
+        .imports
+        .iter()
+        .map(|import| format!("{}.", import.namespace))
+        .collect();
+    for_each_reference(program, |reference| {
+        let known = reference.local
+            || globals.contains(reference.name)
+            || program.function_map.contains_key(reference.name)
+            || namespaces.iter().any(|x| reference.name.starts_with(x));
+        if !known {
+            diagnostics.push(Diagnostic {
+                span: reference.span,
+                severity: Severity::Error,
+                message: format!("Unknown name `{}`", reference.name),
+            });
+        }
+    });
+
+    for function in program.function_map.values() {
+        let (constants, variants): (Vec<_>, Vec<_>) = function
+            .variants
+            .iter()
+            .partition(|x| x.patterns.0.is_empty());
+        if let Some((first, rest)) = variants.split_first() {
+            let arity = first.patterns.0.len();
+            for variant in rest {
+                if variant.patterns.0.len() != arity {
+                    diagnostics.push(Diagnostic {
+                        span: variant.head,
+                        severity: Severity::Warning,
+                        message: format!(
+                            "`{}` takes {} arguments here but {} in its first variant",
+                            function.name,
+                            variant.patterns.0.len(),
+                            arity
+                        ),
+                    });
+                }
+            }
+            if !function.function_value {
+                for variant in constants {
+                    diagnostics.push(Diagnostic {
+                        span: variant.head,
+                        severity: Severity::Warning,
+                        message: format!(
+                            "`{0}` is called without arguments wherever it is named, \
+                             so its other variants are only reachable through `@{0}`; \
+                             define this one as `@{0} = ...` to make `{0}` a function value",
+                            function.name
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|x| x.span.start);
+    diagnostics
+}
+
+/// Calls `f` for every name used in the functions and tests of `program`.
+pub fn for_each_reference<'a>(program: &'a Program, mut f: impl FnMut(Reference<'a>)) {
+    for function in program.function_map.values() {
+        for variant in &function.variants {
+            let locals: HashSet<&str> = variant.patterns.var_names().into_iter().collect();
+            for expr in variant.guard.iter().chain(Some(&variant.body)) {
+                visit(expr, &locals, Some(variant), &mut f);
+            }
+        }
+    }
+    for test in &program.tests {
+        for expr in [&test.actual, &test.expected] {
+            visit(expr, &HashSet::new(), None, &mut f);
+        }
+    }
+}
+
+fn visit<'a>(
+    expr: &'a Expr,
+    locals: &HashSet<&'a str>,
+    variant: Option<&'a FunctionVariant>,
+    f: &mut impl FnMut(Reference<'a>),
+) {
+    match expr {
+        Expr::Variable { name, span, .. } => f(Reference {
+            name,
+            span: *span,
+            local: locals.contains(name.as_str()),
+            variant,
+        }),
+        Expr::Literal(_) => (),
+        Expr::Call { callee, args } => {
+            visit(callee, locals, variant, f);
+            for arg in args {
+                visit(arg, locals, variant, f);
+            }
+        }
+        Expr::Cat { children } => {
+            for child in children {
+                visit(child, locals, variant, f);
+            }
+        }
+        Expr::Let {
+            pattern,
+            value,
+            body,
+        } => {
+            visit(value, locals, variant, f);
+            let mut body_locals = locals.clone();
+            body_locals.extend(pattern.var_names());
+            visit(body, &body_locals, variant, f);
+        }
+    }
+}
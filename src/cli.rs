@@ -0,0 +1,177 @@
+use bitmachine::test_runner::TestOptions;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error(
+    "Usage: {argv0} [run] [--no-prelude] [-I <dir>]... [--profile] [--stacks <file>] [--memo] [--lazy] [--format <format>] <filename> [<argument>...]
+       {argv0} test [--no-prelude] [-I <dir>]... [--step-limit <n>] [--json <file>] [--junit <file>] [--lazy] <path>...
+       {argv0} fmt [--check] <path>...
+       {argv0} debug [--no-prelude] [-I <dir>]... <filename>
+       {argv0} graph [--no-prelude] [-I <dir>]... <filename>
+       {argv0} lsp [--no-prelude]"
+)]
+pub struct UsageError {
+    argv0: String,
+}
+
+pub enum Command {
+    Run {
+        filename: PathBuf,
+        /// Arguments of `main`, as written on the command line.
+        arguments: Vec<String>,
+        /// Where to write the collapsed stacks, if the run is profiled.
+        profile: Option<PathBuf>,
+        /// How to print the result, if it is to be printed.
+        format: Option<Format>,
+        /// Whether every function is memoized.
+        memo: bool,
+        /// Whether every call is lazy.
+        lazy: bool,
+    },
+    Test {
+        paths: Vec<PathBuf>,
+        options: TestOptions,
+    },
+    Fmt {
+        paths: Vec<PathBuf>,
+        check: bool,
+    },
+    Debug {
+        filename: PathBuf,
+    },
+    Graph {
+        filename: PathBuf,
+    },
+    Lsp,
+}
+
+/// Rendering of the result of `main`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Bits,
+    Unsigned,
+    Signed,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Run,
+    Test,
+    Fmt,
+    Debug,
+    Graph,
+    Lsp,
+}
+
+pub struct Options {
+    pub command: Command,
+    pub search_path: Vec<PathBuf>,
+    pub no_prelude: bool,
+}
+
+pub fn parse_args() -> Result<Options, UsageError> {
+    let mut args = std::env::args().peekable();
+    let argv0 = args.next().unwrap();
+    let usage = || UsageError {
+        argv0: argv0.clone(),
+    };
+
+    let mode = match args.peek().map(String::as_str) {
+        Some("test") => Mode::Test,
+        Some("fmt") => Mode::Fmt,
+        Some("debug") => Mode::Debug,
+        Some("graph") => Mode::Graph,
+        Some("lsp") => Mode::Lsp,
+        Some("run") => Mode::Run,
+        _ => {
+            return parse_command_args(args, Mode::Run).ok_or_else(usage);
+        }
+    };
+    args.next();
+    parse_command_args(args, mode).ok_or_else(usage)
+}
+
+fn parse_command_args(mut args: impl Iterator<Item = String>, mode: Mode) -> Option<Options> {
+    let is_test = mode == Mode::Test;
+    let mut paths = Vec::new();
+    let mut search_path = Vec::new();
+    let mut no_prelude = false;
+    let mut test_options = TestOptions::default();
+    let mut check = false;
+    let mut profile = false;
+    let mut stacks = None;
+    let mut format = None;
+    let mut memo = false;
+    let mut lazy = false;
+    let mut arguments = Vec::new();
+
+    while let Some(arg) = args.next() {
+        // Everything after the program goes to `main`, even if it starts with `-`.
+        if mode == Mode::Run && !paths.is_empty() {
+            arguments.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--no-prelude" => no_prelude = true,
+            "-I" => search_path.push(PathBuf::from(args.next()?)),
+            "--step-limit" if is_test => test_options.step_limit = args.next()?.parse().ok()?,
+            "--json" if is_test => test_options.json_report = Some(PathBuf::from(args.next()?)),
+            "--junit" if is_test => test_options.junit_report = Some(PathBuf::from(args.next()?)),
+            "--lazy" if is_test => test_options.lazy = true,
+            "--check" if mode == Mode::Fmt => check = true,
+            "--profile" if mode == Mode::Run => profile = true,
+            "--stacks" if mode == Mode::Run => stacks = Some(PathBuf::from(args.next()?)),
+            "--memo" if mode == Mode::Run => memo = true,
+            "--lazy" if mode == Mode::Run => lazy = true,
+            "--format" if mode == Mode::Run => {
+                format = Some(match args.next()?.as_str() {
+                    "bits" => Format::Bits,
+                    "unsigned" => Format::Unsigned,
+                    "signed" => Format::Signed,
+                    _ => return None,
+                })
+            }
+            _ if arg.starts_with('-') => return None,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let command = match mode {
+        Mode::Run if paths.len() == 1 => {
+            let filename = paths.remove(0);
+            let profile = match stacks {
+                Some(stacks) => Some(stacks),
+                None if profile => Some(Path::new(filename.file_stem()?).with_extension("folded")),
+                None => None,
+            };
+            Command::Run {
+                filename,
+                arguments,
+                profile,
+                format,
+                memo,
+                lazy,
+            }
+        }
+        Mode::Test if !paths.is_empty() => Command::Test {
+            paths,
+            options: test_options,
+        },
+        Mode::Fmt if !paths.is_empty() => Command::Fmt { paths, check },
+        Mode::Debug if paths.len() == 1 => Command::Debug {
+            filename: paths.remove(0),
+        },
+        Mode::Graph if paths.len() == 1 => Command::Graph {
+            filename: paths.remove(0),
+        },
+        Mode::Lsp if paths.is_empty() => Command::Lsp,
+        _ => return None,
+    };
+
+    Some(Options {
+        command,
+        search_path,
+        no_prelude,
+    })
+}
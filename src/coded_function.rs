@@ -2,19 +2,31 @@ use crate::bytecode::Bytecode;
 use crate::pattern::MultiPattern;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodedFunction {
     pub name: String,
     pub variants: Vec<CodedFunctionVariant>,
+    /// Whether results of calls are kept in the memo table of the VM.
+    pub memoized: bool,
+    /// Whether the function is defined by `@name = ...`, and so is not
+    /// called where it is named.
+    pub function_value: bool,
 }
 
 impl CodedFunction {
+    /// Whether naming the function calls it: it has a variant without
+    /// patterns and is not a function value.
     pub fn is_trampoline_callable(&self) -> bool {
-        self.variants.iter().any(|x| x.patterns.0.is_empty())
+        !self.function_value && self.variants.iter().any(|x| x.patterns.0.is_empty())
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodedFunctionVariant {
+    /// Source line of the definition, 0 for generated code.
+    pub line: usize,
     pub patterns: MultiPattern,
+    pub guard: Option<Bytecode>,
     pub body: Bytecode,
 }
@@ -1,18 +1,32 @@
 use crate::bindings::Bindings;
 use crate::callable::Callable;
+use crate::coded_function::CodedFunction;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub function_map: FunctionMap,
+    pub tests: Vec<Test>,
+}
+
+/// A test whose sides are compiled to functions without arguments.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Test {
+    pub name: String,
+    pub line: usize,
+    pub actual: CodedFunction,
+    pub expected: CodedFunction,
 }
 
 pub type FunctionMap = HashMap<String, Callable>;
 
-impl Into<Bindings> for Program {
-    fn into(self) -> Bindings {
+impl From<Program> for Bindings {
+    fn from(program: Program) -> Bindings {
         Bindings::new(
-            self.function_map
+            program
+                .function_map
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
@@ -0,0 +1,325 @@
+//! Interactive step-through debugger driven by line commands.
+
+use crate::ast::Evaluation;
+use crate::bitstring::BitString;
+use crate::bytecode::Pretty;
+use crate::parser;
+use crate::translator::compile_constant;
+use crate::value::Value;
+use crate::vm::{ExecError, Frame, StepEvent, VM};
+use anyhow::Result as AnyResult;
+use std::io::{BufRead, Write};
+
+/// Maximum number of VM steps for evaluating an expression.
+const EVAL_STEP_LIMIT: usize = 1_000_000;
+
+const HELP: &str = "\
+break <function> | <line>   stop when a call starts there
+delete <function> | <line>  remove a breakpoint
+stepi (si)      execute one instruction
+step (s)        run until a call starts or the current one returns
+next (n)        execute one instruction, running calls it makes to completion
+finish (fin)    run until the current call returns
+continue (c)    run until a breakpoint or the end of the program
+stack (bt)      show every frame, innermost first
+frame <n>       show frame n and evaluate expressions in it
+eval <expr>     evaluate an expression in the selected frame
+quit (q)        stop debugging";
+
+#[derive(Debug, PartialEq)]
+enum Breakpoint {
+    Function(String),
+    /// Line of a definition in the debugged file.
+    Line(usize),
+}
+
+enum State {
+    Running,
+    /// Stopped by a runtime error, such as a failed match, at the failing
+    /// instruction, which can still be inspected.
+    Failed(ExecError),
+    Finished,
+}
+
+pub struct Debugger<W: Write> {
+    vm: VM,
+    output: W,
+    breakpoints: Vec<Breakpoint>,
+    /// Frame used by `eval`, counted from the innermost one.
+    selected_frame: usize,
+    state: State,
+}
+
+/// Debugs the call `vm` has been asked to make, with commands read from
+/// `input`. Tracing should be disabled on `vm` beforehand.
+pub fn debug(vm: VM, input: impl BufRead, output: impl Write) -> AnyResult<()> {
+    Debugger::new(vm, output).run(input)
+}
+
+impl<W: Write> Debugger<W> {
+    pub fn new(vm: VM, output: W) -> Debugger<W> {
+        Debugger {
+            vm,
+            output,
+            breakpoints: Vec::new(),
+            selected_frame: 0,
+            state: State::Running,
+        }
+    }
+
+    pub fn run(&mut self, input: impl BufRead) -> AnyResult<()> {
+        self.show_location()?;
+        let mut lines = input.lines();
+        loop {
+            write!(self.output, "(bmdb) ")?;
+            self.output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            let line = line.trim();
+            let (command, argument) = match line.split_once(' ') {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line, ""),
+            };
+
+            match command {
+                "" => (),
+                "break" | "b" => self.set_breakpoint(argument)?,
+                "delete" | "d" => self.delete_breakpoint(argument)?,
+                "stepi" | "si" => self.resume(|_, _, _| true)?,
+                "step" | "s" => self.resume(|_, _, event| event != StepEvent::Instruction)?,
+                "next" | "n" => self.resume(|start, vm, _| vm.depth() <= start)?,
+                "finish" | "fin" => self.resume(|start, vm, _| vm.depth() < start)?,
+                "continue" | "c" => self.resume(|_, _, _| false)?,
+                "stack" | "bt" => self.show_stack()?,
+                "frame" | "f" => self.select_frame(argument)?,
+                "eval" | "e" => self.eval(argument)?,
+                "help" | "h" => writeln!(self.output, "{}", HELP)?,
+                "quit" | "q" => return Ok(()),
+                _ => writeln!(self.output, "Unknown command `{}`, try `help`", command)?,
+            }
+        }
+    }
+
+    fn parse_breakpoint(argument: &str) -> Breakpoint {
+        match argument.parse() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Function(String::from(argument)),
+        }
+    }
+
+    fn set_breakpoint(&mut self, argument: &str) -> AnyResult<()> {
+        if argument.is_empty() {
+            for breakpoint in &self.breakpoints {
+                writeln!(self.output, "{:?}", breakpoint)?;
+            }
+            return Ok(());
+        }
+        let breakpoint = Self::parse_breakpoint(argument);
+        writeln!(self.output, "Breakpoint set: {:?}", breakpoint)?;
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+        Ok(())
+    }
+
+    fn delete_breakpoint(&mut self, argument: &str) -> AnyResult<()> {
+        let breakpoint = Self::parse_breakpoint(argument);
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|x| *x != breakpoint);
+        if self.breakpoints.len() == count {
+            writeln!(self.output, "No such breakpoint: {:?}", breakpoint)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the task that has just started on top of the stack is at a
+    /// breakpoint. Bodies selected by their own guard do not stop again.
+    fn at_breakpoint(&self, event: StepEvent, before: Option<(String, usize)>) -> bool {
+        let frames = self.vm.frames();
+        let frame = match frames.last() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let same_variant = before.is_some_and(|(function, variant)| {
+            function == frame.function() && variant == frame.variant()
+        });
+
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Function(name) => {
+                event == StepEvent::Enter && names_function(name, frame.function())
+            }
+            Breakpoint::Line(line) => {
+                !(event == StepEvent::Select && same_variant)
+                    && frame.line() == *line
+                    && !frame.function().contains('.')
+            }
+        })
+    }
+
+    /// Steps until `done` returns true for the depth of the stack at the
+    /// start, the VM and the last event, or until a breakpoint is hit.
+    fn resume(&mut self, done: impl Fn(usize, &VM, StepEvent) -> bool) -> AnyResult<()> {
+        match &self.state {
+            State::Running => (),
+            State::Failed(e) => {
+                writeln!(self.output, "The program has stopped: {}", e)?;
+                return Ok(());
+            }
+            State::Finished => {
+                writeln!(self.output, "The program has finished")?;
+                return Ok(());
+            }
+        }
+
+        self.selected_frame = 0;
+        let start = self.vm.depth();
+        loop {
+            let before = self
+                .vm
+                .frames()
+                .last()
+                .map(|frame| (String::from(frame.function()), frame.variant()));
+
+            let event = match self.vm.step() {
+                Ok(event) => event,
+                Err(e) => {
+                    writeln!(self.output, "Stopped: {}", e)?;
+                    self.state = State::Failed(e);
+                    return self.show_location();
+                }
+            };
+
+            if self.vm.depth() == 0 {
+                let result = self.vm.result().map_or_else(String::new, Value::to_string);
+                writeln!(self.output, "Program finished: {}", result)?;
+                self.state = State::Finished;
+                return Ok(());
+            }
+            if matches!(event, StepEvent::Enter | StepEvent::Select)
+                && self.at_breakpoint(event, before)
+            {
+                write!(self.output, "Breakpoint: ")?;
+                return self.show_location();
+            }
+            if done(start, &self.vm, event) {
+                return self.show_location();
+            }
+        }
+    }
+
+    fn show_location(&mut self) -> AnyResult<()> {
+        let frames = self.vm.frames();
+        match frames.last() {
+            Some(frame) => writeln!(self.output, "{}", describe(frame))?,
+            None => writeln!(self.output, "No frames")?,
+        }
+        Ok(())
+    }
+
+    fn show_stack(&mut self) -> AnyResult<()> {
+        let frames = self.vm.frames();
+        for (i, frame) in frames.iter().rev().enumerate() {
+            writeln!(self.output, "#{} {}", i, describe(frame))?;
+            show_frame(&mut self.output, frame)?;
+        }
+        Ok(())
+    }
+
+    fn select_frame(&mut self, argument: &str) -> AnyResult<()> {
+        let frames = self.vm.frames();
+        let index = match argument.parse::<usize>() {
+            Ok(index) if index < frames.len() => index,
+            _ => {
+                writeln!(self.output, "No frame `{}`", argument)?;
+                return Ok(());
+            }
+        };
+        self.selected_frame = index;
+        let frame = &frames[frames.len() - 1 - index];
+        writeln!(self.output, "#{} {}", index, describe(frame))?;
+        show_frame(&mut self.output, frame)?;
+        Ok(())
+    }
+
+    /// Evaluates `code` in a fresh VM that sees the locals of the selected frame.
+    fn eval(&mut self, code: &str) -> AnyResult<()> {
+        let expr = match parser::parse_expression(code) {
+            Ok(expr) => expr,
+            Err(e) => {
+                writeln!(self.output, "{:#}", e)?;
+                return Ok(());
+            }
+        };
+
+        let mut bindings = self.vm.global_bindings().clone();
+        let frames = self.vm.frames();
+        if let Some(frame) = frames.iter().rev().nth(self.selected_frame) {
+            bindings = bindings.union_with(frame.local_bindings().clone());
+        }
+
+        let mut vm = VM::new(bindings);
+        let result = vm
+            .invoke(
+                compile_constant(String::from("eval"), expr, Evaluation::Eager).into(),
+                Vec::new(),
+                BitString::empty(),
+                BitString::empty(),
+            )
+            .and_then(|()| vm.run(Some(EVAL_STEP_LIMIT)));
+        match result {
+            Ok(value) => writeln!(self.output, "{}", value)?,
+            Err(e) => writeln!(self.output, "Error: {}", e)?,
+        }
+        Ok(())
+    }
+}
+
+/// Whether a breakpoint on `name` applies to `function`, which may be
+/// qualified with a namespace.
+fn names_function(name: &str, function: &str) -> bool {
+    function == name
+        || function
+            .strip_suffix(name)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn describe(frame: &Frame<'_>) -> String {
+    let mut text = format!("{} variant {}", frame.function(), frame.variant());
+    if frame.line() != 0 {
+        text.push_str(&format!(" (line {})", frame.line()));
+    }
+    if frame.is_guard() {
+        text.push_str(" guard");
+    }
+    if frame.is_argument() {
+        text.push_str(" argument");
+    }
+    match frame.next_instruction() {
+        Some(instruction) => text.push_str(&format!(
+            ", at {}: {}",
+            frame.cursor(),
+            instruction.pretty()
+        )),
+        None => text.push_str(", returning"),
+    }
+    text
+}
+
+fn show_frame(output: &mut impl Write, frame: &Frame<'_>) -> AnyResult<()> {
+    let mut locals: Vec<_> = frame.local_bindings().get_map().iter().collect();
+    locals.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in locals {
+        writeln!(output, "    {} = {}", name, value)?;
+    }
+    let stack: Vec<_> = frame.value_stack().iter().map(Value::to_string).collect();
+    writeln!(output, "    value stack: [{}]", stack.join(", "))?;
+    writeln!(
+        output,
+        "    prepend: {}  append: {}",
+        frame.prepend(),
+        frame.append()
+    )?;
+    Ok(())
+}
@@ -0,0 +1,374 @@
+//! Canonical layout for bitmachine source.
+//!
+//! Source is parsed to a concrete syntax tree that keeps comments and the
+//! spelling of literals, patterns and parentheses, and printed back with
+//! normalized spacing. Variants of a function are moved next to its first
+//! variant, keeping their order, and their `=` signs are aligned.
+
+use crate::module::{self, read_file};
+use crate::parser::{self, BitMachineParser, Pair, Rule};
+use anyhow::{Context, Result as AnyResult};
+use pest::Parser;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+struct SourceLine {
+    item: Item,
+    comment: Option<String>,
+}
+
+enum Item {
+    Empty,
+    Import {
+        path: String,
+        namespace: Option<String>,
+    },
+    /// The kind of pragma followed by its arguments.
+    Pragma(Vec<String>),
+    Test {
+        name: String,
+        actual: Expr,
+        expected: Option<Expr>,
+    },
+    Assert {
+        actual: Expr,
+        expected: Expr,
+    },
+    Variant(Variant),
+}
+
+struct Variant {
+    /// Whether the definition starts with `@`.
+    function_value: bool,
+    name: String,
+    patterns: Vec<String>,
+    guard: Option<Expr>,
+    body: Expr,
+}
+
+enum Expr {
+    Let {
+        pattern: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+    Call(Vec<Expr>),
+    Cat(Vec<Expr>),
+    Paren(Box<Expr>),
+    /// Literal or name, as written.
+    Atom(String),
+}
+
+/// Piece of formatted output. A group holds the variants of one function,
+/// each one with the comment lines that preceded it.
+enum Block {
+    Blank,
+    Line(SourceLine),
+    Group(Vec<(Vec<String>, SourceLine)>),
+}
+
+/// Formats every file in `paths`, which may be files or directories to search
+/// for `.bm` files. With `check`, files are left untouched and the ones that
+/// would change are listed. Returns whether all of them were already formatted.
+pub fn format_files(paths: &[PathBuf], check: bool) -> AnyResult<bool> {
+    let mut all_formatted = true;
+    for path in module::find_sources(paths)? {
+        let code = read_file(&path).with_context(|| format!("Cannot read `{}`", path.display()))?;
+        let formatted = format(&code).with_context(|| format!("In `{}`", path.display()))?;
+        if formatted == code {
+            continue;
+        }
+
+        all_formatted = false;
+        if check {
+            println!("Would reformat `{}`", path.display());
+        } else {
+            std::fs::write(&path, formatted)
+                .with_context(|| format!("Cannot write `{}`", path.display()))?;
+        }
+    }
+    Ok(all_formatted)
+}
+
+pub fn format(code: &str) -> AnyResult<String> {
+    let toplevel = BitMachineParser::parse(Rule::toplevel, code)
+        .map_err(anyhow::Error::from)
+        .with_context(|| String::from("Parse error"))?
+        .next()
+        .unwrap();
+    let program = toplevel.into_inner().next().unwrap();
+    let lines = program.into_inner().map(parse_line);
+
+    let mut out = String::new();
+    let mut last_blank = true;
+    for block in arrange(lines) {
+        let is_blank = matches!(block, Block::Blank);
+        match block {
+            Block::Blank if last_blank => continue,
+            Block::Blank => out.push('\n'),
+            Block::Line(line) => write_line(&mut out, line.item.to_string(), &line.comment),
+            Block::Group(variants) => write_group(&mut out, variants),
+        }
+        last_blank = is_blank;
+    }
+    if out.ends_with("\n\n") {
+        out.pop();
+    }
+
+    parser::parse(&out).context("Formatting produced invalid code")?;
+    Ok(out)
+}
+
+/// Splits `lines` into blocks, moving variants into the group of their
+/// function along with the comment lines directly above them.
+fn arrange(lines: impl Iterator<Item = SourceLine>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let mut comments = Vec::new();
+
+    for line in lines {
+        let variant_name = match (&line.item, &line.comment) {
+            (Item::Empty, Some(comment)) => {
+                comments.push(comment.clone());
+                continue;
+            }
+            (Item::Variant(variant), _) => Some(variant.name.clone()),
+            _ => None,
+        };
+
+        match variant_name {
+            Some(name) => {
+                let leading = std::mem::take(&mut comments);
+                match groups.get(&name) {
+                    Some(&index) => match &mut blocks[index] {
+                        Block::Group(variants) => variants.push((leading, line)),
+                        _ => unreachable!(),
+                    },
+                    None => {
+                        groups.insert(name, blocks.len());
+                        blocks.push(Block::Group(vec![(leading, line)]));
+                    }
+                }
+            }
+            None => {
+                blocks.extend(comments.drain(..).map(comment_line));
+                blocks.push(match line.item {
+                    Item::Empty => Block::Blank,
+                    _ => Block::Line(line),
+                });
+            }
+        }
+    }
+    blocks.extend(comments.into_iter().map(comment_line));
+    blocks
+}
+
+fn comment_line(comment: String) -> Block {
+    Block::Line(SourceLine {
+        item: Item::Empty,
+        comment: Some(comment),
+    })
+}
+
+fn write_group(out: &mut String, variants: Vec<(Vec<String>, SourceLine)>) {
+    let heads: Vec<String> = variants
+        .iter()
+        .map(|(_, line)| match &line.item {
+            Item::Variant(variant) => variant.head(),
+            _ => unreachable!(),
+        })
+        .collect();
+    let width = heads.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+
+    for ((leading, line), head) in variants.into_iter().zip(heads) {
+        for comment in leading {
+            write_line(out, String::new(), &Some(comment));
+        }
+        let body = match line.item {
+            Item::Variant(variant) => variant.body,
+            _ => unreachable!(),
+        };
+        let text = format!("{:width$} = {}", head, body, width = width);
+        write_line(out, text, &line.comment);
+    }
+}
+
+fn write_line(out: &mut String, text: String, comment: &Option<String>) {
+    out.push_str(&text);
+    if let Some(comment) = comment {
+        if !text.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(comment);
+    }
+    out.push('\n');
+}
+
+impl Variant {
+    /// Everything before the `=`.
+    fn head(&self) -> String {
+        let mut head = String::new();
+        if self.function_value {
+            head.push('@');
+        }
+        head.push_str(&self.name);
+        for pattern in &self.patterns {
+            head.push(' ');
+            head.push_str(pattern);
+        }
+        if let Some(guard) = &self.guard {
+            head.push_str(&format!(" | {}", guard));
+        }
+        head
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Empty => Ok(()),
+            Item::Import { path, namespace } => {
+                write!(f, "import {}", path)?;
+                match namespace {
+                    Some(namespace) => write!(f, " as {}", namespace),
+                    None => Ok(()),
+                }
+            }
+            Item::Pragma(words) => write!(f, "pragma {}", words.join(" ")),
+            Item::Test {
+                name,
+                actual,
+                expected,
+            } => {
+                write!(f, "test {} = {}", name, actual)?;
+                match expected {
+                    Some(expected) => write!(f, " == {}", expected),
+                    None => Ok(()),
+                }
+            }
+            Item::Assert { actual, expected } => write!(f, "assert {} == {}", actual, expected),
+            Item::Variant(variant) => write!(f, "{} = {}", variant.head(), variant.body),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, children: &[Expr], separator| {
+            for (i, child) in children.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", separator)?;
+                }
+                write!(f, "{}", child)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            Expr::Let {
+                pattern,
+                value,
+                body,
+            } => write!(f, "let {} = {} in {}", pattern, value, body),
+            Expr::Call(children) => join(f, children, " "),
+            Expr::Cat(children) => join(f, children, "+"),
+            Expr::Paren(inner) => write!(f, "({})", inner),
+            Expr::Atom(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+fn parse_line(line: Pair<'_>) -> SourceLine {
+    let mut iter = line.into_inner();
+    let inner = iter.next().unwrap();
+    let comment = iter.next().map(|x| String::from(x.as_str().trim_end()));
+
+    let item = match inner.as_rule() {
+        Rule::empty_line => Item::Empty,
+        Rule::import => {
+            let mut iter = inner.into_inner();
+            Item::Import {
+                path: String::from(iter.next().unwrap().as_str()),
+                namespace: iter.next().map(|x| String::from(x.as_str())),
+            }
+        }
+        Rule::pragma => Item::Pragma(
+            inner
+                .into_inner()
+                .map(|x| String::from(x.as_str()))
+                .collect(),
+        ),
+        Rule::test_def => {
+            let mut iter = inner.into_inner();
+            Item::Test {
+                name: String::from(iter.next().unwrap().as_str()),
+                actual: parse_expr(iter.next().unwrap()),
+                expected: iter.next().map(parse_expected),
+            }
+        }
+        Rule::assert_def => {
+            let mut iter = inner.into_inner();
+            Item::Assert {
+                actual: parse_expr(iter.next().unwrap()),
+                expected: parse_expected(iter.next().unwrap()),
+            }
+        }
+        Rule::func_def => {
+            let mut iter = inner.into_inner().peekable();
+            let function_value = iter
+                .next_if(|x| x.as_rule() == Rule::function_value)
+                .is_some();
+            let name = String::from(iter.next().unwrap().as_str());
+            let patterns = iter
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(|x| String::from(x.as_str()))
+                .collect();
+            let mut next = iter.next().unwrap();
+            let guard = if next.as_rule() == Rule::guard {
+                let guard = parse_expr(next.into_inner().next().unwrap());
+                next = iter.next().unwrap();
+                Some(guard)
+            } else {
+                None
+            };
+            Item::Variant(Variant {
+                function_value,
+                name,
+                patterns,
+                guard,
+                body: parse_expr(next),
+            })
+        }
+        _ => unreachable!(),
+    };
+
+    SourceLine { item, comment }
+}
+
+fn parse_expected(expected: Pair<'_>) -> Expr {
+    parse_expr(expected.into_inner().next().unwrap())
+}
+
+fn parse_expr(expr: Pair<'_>) -> Expr {
+    match expr.as_rule() {
+        Rule::expr | Rule::expr_single | Rule::expr_atomic => {
+            parse_expr(expr.into_inner().next().unwrap())
+        }
+        Rule::expr_let => {
+            let mut iter = expr.into_inner();
+            Expr::Let {
+                pattern: String::from(iter.next().unwrap().as_str()),
+                value: Box::new(parse_expr(iter.next().unwrap())),
+                body: Box::new(parse_expr(iter.next().unwrap())),
+            }
+        }
+        Rule::expr_call => Expr::Call(expr.into_inner().map(parse_expr).collect()),
+        Rule::expr_cat => Expr::Cat(expr.into_inner().map(parse_expr).collect()),
+        Rule::expr_paren => Expr::Paren(Box::new(parse_expr(expr.into_inner().next().unwrap()))),
+        Rule::expr_literal | Rule::expr_name => Expr::Atom(String::from(expr.as_str())),
+        _ => unreachable!(),
+    }
+}
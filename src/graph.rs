@@ -0,0 +1,249 @@
+//! Call graph of a compiled program, read off its bytecode.
+
+use crate::bindings::Bindings;
+use crate::bytecode::{Bytecode, Instruction};
+use crate::callable::Callable;
+use crate::compiled::Program;
+use crate::value::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallKind {
+    Regular,
+    Tail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A function of the program, with its number of variants.
+    Coded {
+        variants: usize,
+    },
+    /// A function from the prelude or another module that is called but not
+    /// walked.
+    External {
+        variants: usize,
+    },
+    Native,
+}
+
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    pub nodes: BTreeMap<String, NodeKind>,
+    pub edges: BTreeSet<(String, String, CallKind)>,
+}
+
+/// Value on the stack of a bytecode being walked: either a global function
+/// or anything else.
+type Slot<'a> = Option<&'a Callable>;
+
+impl CallGraph {
+    /// Walks every variant of the functions of `program`, resolving the
+    /// names they call in `globals`.
+    pub fn new(program: &Program, globals: &Bindings) -> CallGraph {
+        let mut graph = CallGraph::default();
+        for callable in program.function_map.values() {
+            if let Callable::Coded(function) = callable {
+                graph.nodes.insert(
+                    function.name.clone(),
+                    NodeKind::Coded {
+                        variants: function.variants.len(),
+                    },
+                );
+            }
+        }
+
+        for callable in program.function_map.values() {
+            let function = match callable {
+                Callable::Coded(function) => function,
+                Callable::Native(_) => continue,
+            };
+            for variant in &function.variants {
+                let locals: Vec<String> = variant
+                    .patterns
+                    .var_names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                for bytecode in variant.guard.iter().chain(Some(&variant.body)) {
+                    graph.walk(&function.name, bytecode, locals.clone(), globals);
+                }
+            }
+        }
+        graph
+    }
+
+    fn walk(
+        &mut self,
+        caller: &str,
+        bytecode: &Bytecode,
+        mut locals: Vec<String>,
+        globals: &Bindings,
+    ) {
+        let mut stack: Vec<Slot> = Vec::new();
+        let mut scopes = Vec::new();
+
+        for instruction in bytecode.iter() {
+            match instruction {
+                Instruction::LoadConst(_) => stack.push(None),
+                Instruction::LoadVar { name } => {
+                    let global = match globals.get_value(name) {
+                        Some(Value::Callable(callable)) if !locals.contains(name) => Some(callable),
+                        _ => None,
+                    };
+                    stack.push(global);
+                }
+                Instruction::Trampoline => {
+                    if let Some(Some(Callable::Coded(function))) = stack.last() {
+                        if function.is_trampoline_callable() {
+                            let callee = stack.pop().unwrap();
+                            self.add_edge(caller, callee, CallKind::Regular);
+                            stack.push(None);
+                        }
+                    }
+                }
+                Instruction::Call(num_args) => {
+                    stack.truncate(stack.len().saturating_sub(*num_args));
+                    let callee = stack.pop().flatten();
+                    self.add_edge(caller, callee, CallKind::Regular);
+                    stack.push(None);
+                }
+                // The calls of a delayed argument are made by the caller too,
+                // if they are made at all.
+                Instruction::Delay(bytecode) => {
+                    self.walk(caller, bytecode, locals.clone(), globals);
+                    stack.push(None);
+                }
+                Instruction::Cat(num_children) => {
+                    stack.truncate(stack.len().saturating_sub(*num_children));
+                    stack.push(None);
+                }
+                Instruction::Tail { prepend, append } => {
+                    let callee = stack.get(prepend + append).copied().flatten();
+                    self.add_edge(caller, callee, CallKind::Tail);
+                    stack.clear();
+                }
+                Instruction::Bind(pattern) => {
+                    stack.pop();
+                    let names: Vec<String> =
+                        pattern.var_names().into_iter().map(String::from).collect();
+                    scopes.push(names.len());
+                    locals.extend(names);
+                }
+                Instruction::Unbind => {
+                    let count = scopes.pop().unwrap_or(0);
+                    locals.truncate(locals.len() - count);
+                }
+            }
+        }
+    }
+
+    fn add_edge(&mut self, caller: &str, callee: Slot, kind: CallKind) {
+        let callee = match callee {
+            Some(callee) => callee,
+            None => return,
+        };
+        let name = String::from(callee.name());
+        let node = match callee {
+            Callable::Coded(function) => NodeKind::External {
+                variants: function.variants.len(),
+            },
+            Callable::Native(_) => NodeKind::Native,
+        };
+        self.nodes.entry(name.clone()).or_insert(node);
+        self.edges.insert((String::from(caller), name, kind));
+    }
+
+    fn successors(&self) -> HashMap<&str, Vec<&str>> {
+        let mut successors: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (from, to, _) in &self.edges {
+            successors.entry(from).or_default().push(to);
+        }
+        successors
+    }
+
+    /// Calls that are part of a recursive cycle, including direct recursion.
+    pub fn recursive_calls(&self) -> BTreeSet<(&str, &str)> {
+        let successors = self.successors();
+        self.edges
+            .iter()
+            .filter(|(from, to, _)| reaches(&successors, to, from))
+            .map(|(from, to, _)| (from.as_str(), to.as_str()))
+            .collect()
+    }
+
+    /// Graphviz source with natives as boxes, functions outside the program
+    /// dashed, tail calls as dashed edges and recursive cycles in red.
+    pub fn to_dot(&self) -> String {
+        let cycles = self.recursive_calls();
+        let recursive: BTreeSet<&str> = cycles.iter().map(|(from, _)| *from).collect();
+
+        let mut out = String::from("digraph calls {\n    node [shape=ellipse];\n");
+        for (name, kind) in &self.nodes {
+            let mut attributes = match kind {
+                NodeKind::Coded { variants } => {
+                    vec![format!(
+                        "label=\"{}\\n{}\"",
+                        escape(name),
+                        plural(*variants)
+                    )]
+                }
+                NodeKind::External { variants } => vec![
+                    format!("label=\"{}\\n{}\"", escape(name), plural(*variants)),
+                    String::from("style=dashed"),
+                ],
+                NodeKind::Native => vec![
+                    format!("label=\"{}\\nnative\"", escape(name)),
+                    String::from("shape=box"),
+                ],
+            };
+            if recursive.contains(name.as_str()) {
+                attributes.push(String::from("color=red"));
+            }
+            writeln!(out, "    \"{}\" [{}];", escape(name), attributes.join(", ")).unwrap();
+        }
+        for (from, to, kind) in &self.edges {
+            let mut attributes = Vec::new();
+            if *kind == CallKind::Tail {
+                attributes.push("style=dashed, label=\"tail\"");
+            }
+            if cycles.contains(&(from.as_str(), to.as_str())) {
+                attributes.push("color=red, penwidth=2");
+            }
+            write!(out, "    \"{}\" -> \"{}\"", escape(from), escape(to)).unwrap();
+            if !attributes.is_empty() {
+                write!(out, " [{}]", attributes.join(", ")).unwrap();
+            }
+            out.push_str(";\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Whether `to` can be reached from `from` following `successors`.
+fn reaches(successors: &HashMap<&str, Vec<&str>>, from: &str, to: &str) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![from];
+    while let Some(current) = pending.pop() {
+        if current == to {
+            return true;
+        }
+        if seen.insert(current) {
+            pending.extend(successors.get(current).into_iter().flatten());
+        }
+    }
+    false
+}
+
+fn plural(variants: usize) -> String {
+    match variants {
+        1 => String::from("1 variant"),
+        n => format!("{} variants", n),
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,290 @@
+//! Conversions between bit strings and Rust integers or decimal text.
+
+use crate::bitstring::{Bit, BitString};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+/// How the bits of a number are laid out in a bit string. The default is
+/// the most significant bit first, as in literals. Reversing both orders
+/// reverses the whole string and works for any width; reversing only one
+/// of them requires whole bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Order {
+    pub bits: BitOrder,
+    pub bytes: ByteOrder,
+}
+
+impl Default for Order {
+    fn default() -> Order {
+        Order {
+            bits: BitOrder::MsbFirst,
+            bytes: ByteOrder::BigEndian,
+        }
+    }
+}
+
+impl Order {
+    /// The least significant bit first, for any width.
+    pub fn lsb_first() -> Order {
+        Order {
+            bits: BitOrder::LsbFirst,
+            bytes: ByteOrder::LittleEndian,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("{value} does not fit in {width} bits")]
+    TooNarrow { value: String, width: usize },
+    #[error("{len} bits do not fit in {target}")]
+    TooWide { len: usize, target: &'static str },
+    #[error("{len} bits are not a whole number of bytes")]
+    PartialBytes { len: usize },
+    #[error("Invalid argument `{0}`, expected bits, `.`, `<n>u<width>` or `<n>i<width>`")]
+    InvalidArgument(String),
+}
+
+/// Integer types bit strings convert to and from, in two's complement if
+/// signed.
+pub trait Integer: Copy + std::fmt::Display {
+    const BITS: usize;
+    const SIGNED: bool;
+
+    /// The bits of the number, sign-extended to 128 bits.
+    fn to_bits(self) -> u128;
+
+    /// The number made of the last `Self::BITS` bits of `bits`.
+    fn from_bits(bits: u128) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty: $signed:expr),*) => {
+        $(
+            impl Integer for $t {
+                const BITS: usize = <$t>::BITS as usize;
+                const SIGNED: bool = $signed;
+
+                fn to_bits(self) -> u128 {
+                    // Widening a signed type sign-extends it.
+                    self as i128 as u128
+                }
+
+                fn from_bits(bits: u128) -> Self {
+                    bits as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(
+    u8: false, u16: false, u32: false, u64: false, u128: false, usize: false,
+    i8: true, i16: true, i32: true, i64: true, i128: true, isize: true
+);
+
+impl BitString {
+    /// `value` as a string of `width` bits laid out in `order`.
+    pub fn from_int<T: Integer>(
+        value: T,
+        width: usize,
+        order: Order,
+    ) -> Result<BitString, ConversionError> {
+        let bits = value.to_bits();
+        let negative = T::SIGNED && (bits as i128) < 0;
+        let fits = match width {
+            0 => bits == 0,
+            128.. => true,
+            _ if T::SIGNED => {
+                let value = bits as i128;
+                let bound = 1i128 << (width - 1);
+                -bound <= value && value < bound
+            }
+            _ => bits >> width == 0,
+        };
+        if !fits {
+            return Err(ConversionError::TooNarrow {
+                value: value.to_string(),
+                width,
+            });
+        }
+
+        let mut result = BitString::with_capacity(width);
+        let fill = if negative { u64::MAX } else { 0 };
+        let mut extension = width.saturating_sub(128);
+        while extension > 0 {
+            let count = extension.min(64);
+            result.push_u64(fill, count);
+            extension -= count;
+        }
+        let width = width.min(128);
+        if width > 64 {
+            result.push_u64((bits >> 64) as u64, width - 64);
+        }
+        result.push_u64(bits as u64, width.min(64));
+        reorder(result, order)
+    }
+
+    /// The number the bits laid out in `order` stand for, if it fits in `T`.
+    pub fn to_int<T: Integer>(&self, order: Order) -> Result<T, ConversionError> {
+        let bits = reorder(self.clone(), order)?;
+        let len = bits.len();
+        let negative = T::SIGNED && bits.bit_at(0) == Some(Bit::One);
+
+        // Every bit that does not fit in `T`, and the sign bit of `T`, must
+        // be a copy of the sign bit of the string.
+        let excess = match T::SIGNED {
+            true => (len + 1).saturating_sub(T::BITS),
+            false => len.saturating_sub(T::BITS),
+        };
+        let fill = if negative { u64::MAX } else { 0 };
+        for start in (0..excess).step_by(64) {
+            let count = (excess - start).min(64);
+            if bits.get_u64(start, count) != fill >> (64 - count) {
+                return Err(ConversionError::TooWide {
+                    len,
+                    target: std::any::type_name::<T>(),
+                });
+            }
+        }
+
+        let low = len.min(128);
+        let mut value = 0u128;
+        for start in (len - low..len).step_by(64) {
+            let count = (len - start).min(64);
+            value = (value << count) | bits.get_u64(start, count) as u128;
+        }
+        if negative && low < 128 {
+            value |= u128::MAX << low;
+        }
+        Ok(T::from_bits(value))
+    }
+
+    /// The number the bits stand for in decimal, in two's complement if
+    /// `signed`. The empty string stands for 0.
+    pub fn to_decimal(&self, signed: bool) -> String {
+        let negative = signed && self.bit_at(0) == Some(Bit::One);
+
+        // Little-endian base-2^32 limbs.
+        let mut limbs: Vec<u32> = Vec::with_capacity(self.len().div_ceil(32));
+        let mut end = self.len();
+        while end > 0 {
+            let start = end.saturating_sub(32);
+            limbs.push(self.get_u64(start, end - start) as u32);
+            end = start;
+        }
+        if negative {
+            // Negate in two's complement: invert the bits of the string, then
+            // add one.
+            for limb in limbs.iter_mut() {
+                *limb = !*limb;
+            }
+            let top_bits = self.len() % 32;
+            if top_bits != 0 {
+                *limbs.last_mut().unwrap() &= (1 << top_bits) - 1;
+            }
+            for limb in limbs.iter_mut() {
+                let (sum, overflow) = limb.overflowing_add(1);
+                *limb = sum;
+                if !overflow {
+                    break;
+                }
+            }
+        }
+
+        let mut chunks = Vec::new();
+        while limbs.iter().any(|&limb| limb != 0) {
+            let mut remainder = 0u64;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 32) | *limb as u64;
+                *limb = (value / 1_000_000_000) as u32;
+                remainder = value % 1_000_000_000;
+            }
+            chunks.push(remainder as u32);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        let mut text = String::from(if negative { "-" } else { "" });
+        match chunks.split_last() {
+            Some((first, rest)) => {
+                text.push_str(&first.to_string());
+                for chunk in rest.iter().rev() {
+                    text.push_str(&format!("{:09}", chunk));
+                }
+            }
+            None => text.push('0'),
+        }
+        text
+    }
+}
+
+/// Converts between the default order and `order`. Applying it twice gives
+/// back the original string.
+fn reorder(bits: BitString, order: Order) -> Result<BitString, ConversionError> {
+    let reverse_bytes = order.bytes == ByteOrder::LittleEndian;
+    let reverse_bits = order.bits == BitOrder::LsbFirst;
+    if reverse_bytes && reverse_bits {
+        return Ok(bits.into_iter().rev().collect());
+    }
+    if !reverse_bytes && !reverse_bits {
+        return Ok(bits);
+    }
+
+    let len = bits.len();
+    if !len.is_multiple_of(8) {
+        return Err(ConversionError::PartialBytes { len });
+    }
+    let mut result = BitString::with_capacity(len);
+    for i in 0..len / 8 {
+        let byte = match reverse_bytes {
+            true => bits.get_u64(len - 8 * (i + 1), 8) as u8,
+            false => (bits.get_u64(8 * i, 8) as u8).reverse_bits(),
+        };
+        result.push_u64(byte as u64, 8);
+    }
+    Ok(result)
+}
+
+/// A value given on the command line: bits such as `1011`, `.` for the
+/// empty string, or a decimal number with a width, `42u8` or `-3i4`.
+pub fn parse_argument(text: &str) -> Result<BitString, ConversionError> {
+    let invalid = || ConversionError::InvalidArgument(String::from(text));
+    if text.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(bits) = text.parse() {
+        return Ok(bits);
+    }
+
+    let (number, signed, width) = match text.rfind(['u', 'i']) {
+        Some(index) => (
+            &text[..index],
+            &text[index..index + 1] == "i",
+            text[index + 1..].parse().map_err(|_| invalid())?,
+        ),
+        None => return Err(invalid()),
+    };
+    match signed {
+        true => {
+            let value: i128 = number.parse().map_err(|_| invalid())?;
+            BitString::from_int(value, width, Order::default())
+        }
+        false => {
+            let value: u128 = number.parse().map_err(|_| invalid())?;
+            BitString::from_int(value, width, Order::default())
+        }
+    }
+}
@@ -0,0 +1,51 @@
+pub mod ast;
+pub mod bindings;
+pub mod bitstring;
+pub mod bytecode;
+pub mod callable;
+pub mod check;
+pub mod coded_function;
+pub mod compiled;
+pub mod debugger;
+pub mod formatter;
+pub mod graph;
+pub mod integer;
+pub mod literal;
+pub mod lsp;
+pub mod module;
+pub mod native_function;
+pub mod parser;
+pub mod pattern;
+pub mod prelude;
+pub mod profile;
+pub mod property;
+pub mod rope;
+pub mod test_runner;
+pub mod thunk;
+pub mod translator;
+pub mod value;
+pub mod vm;
+
+use crate::bindings::Bindings;
+use crate::compiled::Program as CompiledProgram;
+use std::collections::{HashMap, HashSet};
+
+/// Bindings visible to a program: its own functions, the natives and,
+/// unless `prelude` is empty, the prelude.
+pub fn make_global_bindings(program: CompiledProgram, prelude: Bindings) -> Bindings {
+    prelude
+        .union_with(program.into())
+        .union_with(native_function::make_bindings())
+}
+
+/// Names bound outside of a program: the natives and the prelude.
+pub fn global_names(prelude: Bindings) -> HashSet<String> {
+    let empty = CompiledProgram {
+        function_map: HashMap::new(),
+        tests: Vec::new(),
+    };
+    make_global_bindings(empty, prelude)
+        .into_map()
+        .into_keys()
+        .collect()
+}
@@ -0,0 +1,101 @@
+use crate::bitstring::{Bit, BitString};
+use std::iter;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LiteralError {
+    #[error("Literal `{literal}` does not fit in {width} bits")]
+    TooWide { literal: String, width: usize },
+    #[error("Character {0:?} does not fit in 8 bits")]
+    CharTooWide(char),
+    #[error("Width `{0}` is too large")]
+    WidthTooLarge(String),
+}
+
+/// Bits of a hexadecimal or octal number, `bits_per_digit` bits per digit.
+/// Leading zero digits are kept, so `0x0f` is 8 bits long.
+pub fn from_power_of_two_digits(digits: &str, bits_per_digit: u32) -> BitString {
+    digits
+        .chars()
+        .flat_map(|c| {
+            let digit = c.to_digit(1 << bits_per_digit).unwrap();
+            (0..bits_per_digit)
+                .rev()
+                .map(move |i| Bit::from_number(((digit >> i) & 1) as u8).unwrap())
+        })
+        .collect()
+}
+
+/// Shortest bit representation of a decimal number of any magnitude.
+pub fn from_decimal_digits(digits: &str) -> BitString {
+    // Little-endian base-2^32 limbs.
+    let mut limbs: Vec<u32> = Vec::new();
+    for c in digits.chars() {
+        let mut carry = u64::from(c.to_digit(10).unwrap());
+        for limb in limbs.iter_mut() {
+            let value = u64::from(*limb) * 10 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry != 0 {
+            limbs.push(carry as u32);
+        }
+    }
+
+    limbs
+        .iter()
+        .rev()
+        .flat_map(|&limb| (0..32).rev().map(move |i| (limb >> i) & 1))
+        .skip_while(|&bit| bit == 0)
+        .map(|bit| Bit::from_number(bit as u8).unwrap())
+        .collect()
+}
+
+/// Pads `bits` with leading zeros or strips them to make it exactly `width` bits long.
+pub fn fit_to_width(
+    bits: BitString,
+    width: usize,
+    literal: &str,
+) -> Result<BitString, LiteralError> {
+    let len = bits.len();
+    if len <= width {
+        Ok(iter::repeat_n(Bit::Zero, width - len).chain(bits).collect())
+    } else if bits.iter().take(len - width).all(|bit| bit == Bit::Zero) {
+        Ok(bits.into_iter().skip(len - width).collect())
+    } else {
+        Err(LiteralError::TooWide {
+            literal: String::from(literal),
+            width,
+        })
+    }
+}
+
+/// The 8-bit code of a character literal.
+pub fn from_char(c: char) -> Result<BitString, LiteralError> {
+    let code = u32::from(c);
+    if code > 0xff {
+        return Err(LiteralError::CharTooWide(c));
+    }
+    Ok(from_bytes(&[code as u8]))
+}
+
+/// Bits of a byte sequence, most significant bit of each byte first.
+pub fn from_bytes(bytes: &[u8]) -> BitString {
+    BitString::from_bytes(bytes)
+}
+
+/// The character denoted by a single (possibly escaped) character of a
+/// character or string literal.
+pub fn unescape(item: &str) -> char {
+    let mut chars = item.chars();
+    match chars.next().unwrap() {
+        '\\' => match chars.next().unwrap() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            c => c,
+        },
+        c => c,
+    }
+}
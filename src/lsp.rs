@@ -0,0 +1,504 @@
+//! Language server speaking LSP over stdio.
+//!
+//! Documents are kept in full and parsed again on every change. Requests
+//! are answered from the parsed program, so a document that does not parse
+//! only gets diagnostics.
+
+use crate::ast::{FunctionVariant, Program, Span};
+use crate::check::{self, Diagnostic, Severity};
+use crate::global_names;
+use crate::parser::{self, Rule, SourceContext};
+use crate::prelude;
+use anyhow::{bail, Result as AnyResult};
+use serde_json::{json, Value as Json};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+struct Document {
+    text: String,
+    program: Option<Program>,
+}
+
+pub struct Server {
+    documents: HashMap<String, Document>,
+    /// Names bound outside of the documents: natives and the prelude.
+    globals: HashSet<String>,
+    /// Parsed prelude, for hovering over its functions.
+    prelude: Option<Program>,
+}
+
+/// Serves requests read from `input` until the client sends `exit`.
+pub fn serve(input: impl BufRead, output: impl Write, with_prelude: bool) -> AnyResult<()> {
+    Server::new(with_prelude).serve(input, output)
+}
+
+impl Server {
+    pub fn new(with_prelude: bool) -> Server {
+        let prelude_bindings = if with_prelude {
+            prelude::make_bindings()
+        } else {
+            crate::bindings::Bindings::empty()
+        };
+        Server {
+            documents: HashMap::new(),
+            globals: global_names(prelude_bindings),
+            prelude: if with_prelude {
+                Some(prelude::parse())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn serve(&mut self, mut input: impl BufRead, mut output: impl Write) -> AnyResult<()> {
+        while let Some(message) = read_message(&mut input)? {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": Json::Null,
+                        "error": {"code": PARSE_ERROR, "message": error},
+                    });
+                    write_message(&mut output, &response)?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+            let id = message.get("id");
+
+            let (result, notifications) = match method {
+                "exit" => return Ok(()),
+                "initialize" => (Ok(capabilities()), Vec::new()),
+                "shutdown" => (Ok(Json::Null), Vec::new()),
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    let uri = string(&document["uri"]);
+                    let text = string(&document["text"]);
+                    (Ok(Json::Null), vec![self.update(uri, text)])
+                }
+                "textDocument/didChange" => {
+                    let uri = string(&params["textDocument"]["uri"]);
+                    match params["contentChanges"].as_array().and_then(|x| x.last()) {
+                        Some(change) => (
+                            Ok(Json::Null),
+                            vec![self.update(uri, string(&change["text"]))],
+                        ),
+                        None => (Ok(Json::Null), Vec::new()),
+                    }
+                }
+                "textDocument/didClose" => {
+                    let uri = string(&params["textDocument"]["uri"]);
+                    self.documents.remove(&uri);
+                    (Ok(Json::Null), vec![publish_diagnostics(&uri, Vec::new())])
+                }
+                "textDocument/definition" => (self.at_position(params, definition), Vec::new()),
+                "textDocument/hover" => (self.at_position(params, hover), Vec::new()),
+                "textDocument/completion" => (self.at_position(params, completion), Vec::new()),
+                "textDocument/documentSymbol" => (
+                    Ok(self.document(params).map_or(Json::Null, document_symbols)),
+                    Vec::new(),
+                ),
+                _ => (Err(method), Vec::new()),
+            };
+
+            if let Some(id) = id {
+                let response = match result {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Err(method) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": METHOD_NOT_FOUND,
+                            "message": format!("Unknown method `{}`", method),
+                        },
+                    }),
+                };
+                write_message(&mut output, &response)?;
+            }
+            for notification in notifications {
+                write_message(&mut output, &notification)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `text` as the new content of `uri` and returns the diagnostics
+    /// notification for it.
+    fn update(&mut self, uri: String, text: String) -> Json {
+        let (program, diagnostics) = match parser::parse(&text) {
+            Ok(program) => {
+                let diagnostics = check::check(&program, &self.globals)
+                    .iter()
+                    .map(|x| diagnostic_json(&text, x))
+                    .collect();
+                (Some(program), diagnostics)
+            }
+            Err(e) => (None, vec![parse_error_json(&text, &e)]),
+        };
+        let notification = publish_diagnostics(&uri, diagnostics);
+        self.documents.insert(uri, Document { text, program });
+        notification
+    }
+
+    fn document(&self, params: &Json) -> Option<&Document> {
+        self.documents
+            .get(params["textDocument"]["uri"].as_str()?)
+            .filter(|x| x.program.is_some())
+    }
+
+    fn at_position(
+        &self,
+        params: &Json,
+        handler: fn(&Server, &Document, &str, usize) -> Json,
+    ) -> Result<Json, &'static str> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        Ok(match self.document(params) {
+            Some(document) => {
+                let offset = offset_of(&document.text, &params["position"]);
+                handler(self, document, uri, offset)
+            }
+            None => Json::Null,
+        })
+    }
+}
+
+fn capabilities() -> Json {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {},
+            "documentSymbolProvider": true,
+        },
+        "serverInfo": {"name": "bitmachine"},
+    })
+}
+
+/// Global function named at `offset`, either where it is used or where one
+/// of its variants is defined.
+fn function_at(program: &Program, offset: usize) -> Option<&str> {
+    let mut found = None;
+    check::for_each_reference(program, |reference| {
+        if !reference.local && reference.span.contains(offset) {
+            found = Some(reference.name);
+        }
+    });
+    found.or_else(|| {
+        program
+            .function_map
+            .values()
+            .find(|function| {
+                function.variants.iter().any(|variant| {
+                    let start = variant.head.start;
+                    Span {
+                        start,
+                        end: start + function.name.len(),
+                    }
+                    .contains(offset)
+                })
+            })
+            .map(|function| function.name.as_str())
+    })
+}
+
+fn definition(_: &Server, document: &Document, uri: &str, offset: usize) -> Json {
+    let program = document.program.as_ref().unwrap();
+    let function = function_at(program, offset).and_then(|name| program.function_map.get(name));
+    match function {
+        Some(function) => function
+            .variants
+            .iter()
+            .map(|variant| json!({"uri": uri, "range": range(&document.text, variant.span)}))
+            .collect(),
+        None => Json::Null,
+    }
+}
+
+fn hover(server: &Server, document: &Document, _: &str, offset: usize) -> Json {
+    let program = document.program.as_ref().unwrap();
+    let name = match function_at(program, offset) {
+        Some(name) => name,
+        None => return Json::Null,
+    };
+
+    let short_name = name
+        .strip_prefix(prelude::NAMESPACE)
+        .and_then(|x| x.strip_prefix('.'));
+    let (source, function) = match (program.function_map.get(name), &server.prelude) {
+        (Some(function), _) => (document.text.as_str(), function),
+        (None, Some(prelude)) => match prelude.function_map.get(short_name.unwrap_or(name)) {
+            Some(function) => (prelude::SOURCE, function),
+            None => return Json::Null,
+        },
+        (None, None) => return Json::Null,
+    };
+
+    let heads: Vec<&str> = function
+        .variants
+        .iter()
+        .map(|variant| &source[variant.head.start..variant.head.end])
+        .collect();
+    json!({
+        "contents": {
+            "kind": "markdown",
+            "value": format!("```\n{}\n```", heads.join("\n")),
+        }
+    })
+}
+
+fn completion(server: &Server, document: &Document, _: &str, offset: usize) -> Json {
+    let program = document.program.as_ref().unwrap();
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add = |name: &str, kind: u32| {
+        if seen.insert(name.to_owned()) {
+            items.push(json!({"label": name, "kind": kind}));
+        }
+    };
+
+    const FUNCTION: u32 = 3;
+    const VARIABLE: u32 = 6;
+
+    let variant = program
+        .function_map
+        .values()
+        .flat_map(|function| &function.variants)
+        .find(|variant| variant.span.contains(offset));
+    if let Some(variant) = variant {
+        for name in local_names(variant) {
+            add(name, VARIABLE);
+        }
+    }
+
+    let mut globals: Vec<&str> = program
+        .function_map
+        .keys()
+        .chain(&server.globals)
+        .map(String::as_str)
+        .collect();
+    globals.sort_unstable();
+    for name in globals {
+        add(name, FUNCTION);
+    }
+    Json::Array(items)
+}
+
+/// Names bound by the patterns of `variant` and by the `let`s in it.
+fn local_names(variant: &FunctionVariant) -> Vec<&str> {
+    fn visit<'a>(expr: &'a crate::ast::Expr, names: &mut Vec<&'a str>) {
+        use crate::ast::Expr;
+        match expr {
+            Expr::Variable { .. } | Expr::Literal(_) => (),
+            Expr::Call { callee, args } => {
+                visit(callee, names);
+                args.iter().for_each(|x| visit(x, names));
+            }
+            Expr::Cat { children } => children.iter().for_each(|x| visit(x, names)),
+            Expr::Let {
+                pattern,
+                value,
+                body,
+            } => {
+                names.extend(pattern.var_names());
+                visit(value, names);
+                visit(body, names);
+            }
+        }
+    }
+
+    let mut names = variant.patterns.var_names();
+    for expr in variant.guard.iter().chain(Some(&variant.body)) {
+        visit(expr, &mut names);
+    }
+    names
+}
+
+fn document_symbols(document: &Document) -> Json {
+    const FUNCTION: u32 = 12;
+
+    let text = &document.text;
+    let program = document.program.as_ref().unwrap();
+    let mut functions: Vec<_> = program.function_map.values().collect();
+    functions.sort_by_key(|function| function.variants[0].span.start);
+
+    functions
+        .into_iter()
+        .map(|function| {
+            let first = &function.variants[0];
+            let span = Span {
+                start: first.span.start,
+                end: function.variants.iter().map(|x| x.span.end).max().unwrap(),
+            };
+            let children: Vec<Json> = function
+                .variants
+                .iter()
+                .map(|variant| {
+                    json!({
+                        "name": &text[variant.head.start..variant.head.end],
+                        "kind": FUNCTION,
+                        "range": range(text, variant.span),
+                        "selectionRange": range(text, variant.head),
+                    })
+                })
+                .collect();
+            json!({
+                "name": function.name,
+                "kind": FUNCTION,
+                "range": range(text, span),
+                "selectionRange": range(text, Span {
+                    start: first.head.start,
+                    end: first.head.start + function.name.len(),
+                }),
+                "children": children,
+            })
+        })
+        .collect()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn diagnostic_json(text: &str, diagnostic: &Diagnostic) -> Json {
+    json!({
+        "range": range(text, diagnostic.span),
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "source": "bitmachine",
+        "message": diagnostic.message,
+    })
+}
+
+fn parse_error_json(text: &str, error: &anyhow::Error) -> Json {
+    let (range, message) = if let Some(e) = error.downcast_ref::<pest::error::Error<Rule>>() {
+        let ((line, column), end) = match e.line_col {
+            pest::error::LineColLocation::Pos(start) => (start, start),
+            pest::error::LineColLocation::Span(start, end) => (start, end),
+        };
+        let position = |(line, column): (usize, usize)| {
+            let line_text = text.split('\n').nth(line - 1).unwrap_or_default();
+            let character: usize = line_text
+                .chars()
+                .take(column - 1)
+                .map(char::len_utf16)
+                .sum();
+            json!({"line": line - 1, "character": character})
+        };
+        (
+            json!({"start": position((line, column)), "end": position(end)}),
+            e.variant.message().into_owned(),
+        )
+    } else if let Some(context) = error.downcast_ref::<SourceContext>() {
+        (
+            range(text, context.span),
+            format!("{}: {}", context.message, error.root_cause()),
+        )
+    } else {
+        (range(text, Span::default()), format!("{:#}", error))
+    };
+
+    json!({
+        "range": range,
+        "severity": 1,
+        "source": "bitmachine",
+        "message": message,
+    })
+}
+
+fn range(text: &str, span: Span) -> Json {
+    json!({"start": position(text, span.start), "end": position(text, span.end)})
+}
+
+/// LSP position of the byte `offset`, counting characters in UTF-16 units.
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({"line": line, "character": character})
+}
+
+/// Byte offset of an LSP position, clamped to the end of its line.
+fn offset_of(text: &str, position: &Json) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+    let line_start: usize = text.split('\n').take(line).map(|x| x.len() + 1).sum();
+    if line_start > text.len() {
+        return text.len();
+    }
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn string(value: &Json) -> String {
+    String::from(value.as_str().unwrap_or_default())
+}
+
+/// Reads the next message, or returns `None` at the end of the input. A
+/// message that cannot be understood is returned as the reason why, so that
+/// the client can be told and the server can go on.
+fn read_message(input: &mut impl BufRead) -> AnyResult<Option<Result<Json, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let value = value.trim();
+                length = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid Content-Length `{}`", value)),
+                );
+            }
+        }
+    }
+
+    let length = match length {
+        Some(Ok(x)) => x,
+        Some(Err(e)) => return Ok(Some(Err(e))),
+        None => return Ok(Some(Err(String::from("Message without Content-Length")))),
+    };
+    // The body is read as it arrives rather than allocated up front, since
+    // the length comes from the client.
+    let mut body = Vec::new();
+    input.take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length {
+        bail!("The input ended inside a message");
+    }
+    Ok(Some(
+        serde_json::from_slice(&body).map_err(|e| format!("Invalid JSON-RPC message: {}", e)),
+    ))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> AnyResult<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
@@ -1,68 +1,161 @@
-mod ast;
-mod bindings;
-mod bitstring;
-mod bytecode;
-mod callable;
-mod coded_function;
-mod compiled;
-mod native_function;
-mod parser;
-mod pattern;
-mod translator;
-mod value;
-mod vm;
+mod cli;
 
-use crate::bindings::Bindings;
-use crate::bitstring::BitString;
-use crate::translator::Compile;
-use std::io::Read;
+use crate::cli::{Command, Format};
+use bitmachine::ast::Evaluation;
+use bitmachine::bindings::Bindings;
+use bitmachine::graph::CallGraph;
+use bitmachine::module::ModuleLoader;
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::{
+    debugger, formatter, global_names, integer, lsp, make_global_bindings, prelude, test_runner, vm,
+};
 use std::path::Path;
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-#[error("Usage: {argv0} <filename>")]
-struct UsageError {
-    argv0: String,
-}
-
-fn read_file(path: &Path) -> std::io::Result<String> {
-    let mut file = std::fs::File::open(path)?;
-    let mut data = String::new();
-    file.read_to_string(&mut data)?;
-    Ok(data)
-}
 
 fn main() -> anyhow::Result<()> {
-    let filename = {
-        let mut args = std::env::args();
-        let argv0 = args.next().unwrap();
-        args.next().ok_or(UsageError { argv0 })?
+    let options = cli::parse_args()?;
+    let mut loader = ModuleLoader::with_env_search_path(options.search_path);
+    let prelude_bindings = if options.no_prelude {
+        Bindings::empty()
+    } else {
+        prelude::make_bindings()
     };
-    let code = read_file(Path::new(&filename))?;
 
-    let program = parser::parse(&code)?;
+    if matches!(options.command, Command::Run { .. } | Command::Test { .. }) {
+        loader.check_against(global_names(prelude_bindings.clone()));
+    }
+
+    match options.command {
+        Command::Run {
+            filename,
+            arguments,
+            profile,
+            format,
+            memo,
+            lazy,
+        } => {
+            let arguments = arguments
+                .iter()
+                .map(|x| integer::parse_argument(x).map(Value::from))
+                .collect::<Result<_, _>>()?;
+            let result = run(
+                &filename,
+                &mut loader,
+                prelude_bindings,
+                arguments,
+                profile.as_deref(),
+                format.is_none(),
+                memo,
+                lazy,
+            )?;
+            if let (Some(format), Some(result)) = (format, result) {
+                println!("{}", render(&result, format));
+            }
+            Ok(())
+        }
+        Command::Test {
+            paths,
+            options: test_options,
+        } => {
+            let all_passed =
+                test_runner::run_tests(&paths, &mut loader, prelude_bindings, &test_options)?;
+            if !all_passed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Fmt { paths, check } => {
+            let all_formatted = formatter::format_files(&paths, check)?;
+            if check && !all_formatted {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Debug { filename } => {
+            let mut vm = load(&filename, &mut loader, prelude_bindings)?;
+            vm.invoke_by_name("main", vec![])?;
+            let stdin = std::io::stdin();
+            debugger::debug(vm, stdin.lock(), std::io::stdout())
+        }
+        Command::Graph { filename } => {
+            let program = loader.load(&filename)?.compile();
+            let globals = make_global_bindings(program.clone(), prelude_bindings);
+            print!("{}", CallGraph::new(&program, &globals).to_dot());
+            Ok(())
+        }
+        Command::Lsp => {
+            let stdin = std::io::stdin();
+            lsp::serve(stdin.lock(), std::io::stdout(), !options.no_prelude)
+        }
+    }
+}
+
+/// VM with the program in `filename` and everything it imports.
+fn load(
+    filename: &Path,
+    loader: &mut ModuleLoader,
+    prelude_bindings: Bindings,
+) -> anyhow::Result<vm::VM> {
+    let program = loader.load(filename)?;
     let compiled_program = program.compile();
+    Ok(vm::VM::new(make_global_bindings(
+        compiled_program,
+        prelude_bindings,
+    )))
+}
 
-    dbg!(&compiled_program);
+/// Runs `main` with `arguments` and returns its result. If `stacks` is set,
+/// the trace is replaced by a profile printed to stderr and collapsed
+/// stacks written to `stacks`. Without `trace`, nothing is printed while
+/// running. With `memoize_all`, every function is memoized; with `lazy`,
+/// every call is lazy.
+#[allow(clippy::too_many_arguments)]
+fn run(
+    filename: &Path,
+    loader: &mut ModuleLoader,
+    prelude_bindings: Bindings,
+    arguments: Vec<Value>,
+    stacks: Option<&Path>,
+    trace: bool,
+    memoize_all: bool,
+    lazy: bool,
+) -> anyhow::Result<Option<Value>> {
+    let mut program = loader.load(filename)?;
+    if lazy {
+        program.set_evaluation(Evaluation::Lazy);
+    }
+    let compiled_program = program.compile();
 
-    let mut vm = vm::VM::new(
-        Bindings::new(
-            vec![("test", BitString::empty().into())]
-                .into_iter()
-                .map(|(k, v)| (k.to_owned(), v))
-                .collect(),
-        )
-        .union_with(compiled_program.into())
-        .union_with(native_function::make_bindings()),
-    );
+    let mut vm = vm::VM::new(make_global_bindings(compiled_program, prelude_bindings));
+    if stacks.is_some() {
+        vm.enable_profiling();
+    } else {
+        vm.set_trace(trace);
+    }
+    vm.set_memoize_all(memoize_all);
 
-    vm.invoke_by_name("main", vec![])?;
+    vm.invoke_by_name("main", arguments)?;
     loop {
         match vm.step() {
             Err(vm::ExecError::TaskStackEmpty) => break,
-            x => x?,
+            x => {
+                x?;
+            }
         }
     }
 
-    Ok(())
+    if let (Some(stacks), Some(profile)) = (stacks, vm.profile()) {
+        eprint!("{}", profile.report());
+        std::fs::write(stacks, profile.collapsed_stacks())?;
+        eprintln!("Collapsed stacks written to {}", stacks.display());
+    }
+    Ok(vm.result().cloned())
+}
+
+fn render(value: &Value, format: Format) -> String {
+    match (value, format) {
+        (Value::BitString(s), Format::Unsigned) => s.to_decimal(false),
+        (Value::BitString(s), Format::Signed) => s.to_decimal(true),
+        _ => value.to_string(),
+    }
 }
@@ -0,0 +1,273 @@
+use crate::ast::{Expr, Function, FunctionMap, FunctionVariant, Program};
+use crate::check::{self, Severity};
+use crate::parser;
+use anyhow::{anyhow, bail, Context, Result as AnyResult};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding extra directories to search for imports,
+/// separated the same way as `PATH`.
+pub const SEARCH_PATH_VAR: &str = "BITMACHINE_PATH";
+
+pub fn read_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut data = String::new();
+    file.read_to_string(&mut data)?;
+    Ok(data)
+}
+
+/// Lists `paths` in order, replacing each directory with the `.bm` files
+/// under it, sorted.
+pub fn find_sources(paths: &[PathBuf]) -> AnyResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        discover(path, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn discover(path: &Path, files: &mut Vec<PathBuf>) -> AnyResult<()> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = std::fs::read_dir(path)
+        .with_context(|| format!("Cannot read `{}`", path.display()))?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "bm") {
+            discover(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Loads a program together with everything it imports.
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    /// Files currently being loaded, outermost first.
+    stack: Vec<PathBuf>,
+    /// Names bound outside of the loaded files, if they are to be checked.
+    globals: Option<HashSet<String>>,
+}
+
+impl ModuleLoader {
+    pub fn new(search_path: Vec<PathBuf>) -> ModuleLoader {
+        ModuleLoader {
+            search_path,
+            stack: Vec::new(),
+            globals: None,
+        }
+    }
+
+    /// Checks every file loaded from now on, with `globals` bound outside of
+    /// it, and prints what is found to stderr.
+    pub fn check_against(&mut self, globals: HashSet<String>) {
+        self.globals = Some(globals);
+    }
+
+    /// Search path consisting of `extra_dirs` followed by the directories
+    /// listed in `BITMACHINE_PATH`.
+    pub fn with_env_search_path(extra_dirs: Vec<PathBuf>) -> ModuleLoader {
+        let env_dirs = std::env::var_os(SEARCH_PATH_VAR)
+            .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+            .unwrap_or_default();
+        ModuleLoader::new(extra_dirs.into_iter().chain(env_dirs).collect())
+    }
+
+    /// Parses the file at `path`. Functions of imported files are added to
+    /// the result under their qualified names.
+    pub fn load(&mut self, path: &Path) -> AnyResult<Program> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Cannot open `{}`", path.display()))?;
+        if let Some(pos) = self.stack.iter().position(|x| *x == canonical) {
+            let cycle: Vec<_> = self.stack[pos..]
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|x| x.display().to_string())
+                .collect();
+            bail!("Import cycle: {}", cycle.join(" -> "));
+        }
+
+        self.stack.push(canonical);
+        let result = self.load_unchecked(path);
+        self.stack.pop();
+        result
+    }
+
+    fn load_unchecked(&mut self, path: &Path) -> AnyResult<Program> {
+        let code = read_file(path).with_context(|| format!("Cannot read `{}`", path.display()))?;
+        let program = parser::parse(&code).with_context(|| format!("In `{}`", path.display()))?;
+        if let Some(globals) = &self.globals {
+            for diagnostic in check::check(&program, globals) {
+                let (line, column) = line_col(&code, diagnostic.span.start);
+                let severity = match diagnostic.severity {
+                    Severity::Error => "Error",
+                    Severity::Warning => "Warning",
+                };
+                eprintln!(
+                    "{} in `{}`: {} at line {}, column {}",
+                    severity,
+                    path.display(),
+                    diagnostic.message,
+                    line,
+                    column
+                );
+            }
+        }
+
+        let mut function_map = program.function_map;
+        let mut namespaces = HashSet::new();
+        for import in program.imports {
+            if !namespaces.insert(import.namespace.clone()) {
+                bail!(
+                    "Namespace `{}` is imported more than once in `{}`",
+                    import.namespace,
+                    path.display()
+                );
+            }
+
+            let resolved = self.resolve(&import.path, path)?;
+            let imported = self
+                .load(&resolved)
+                .with_context(|| format!("Imported from `{}`", path.display()))?;
+            function_map.extend(qualify(imported.function_map, &import.namespace));
+        }
+
+        Ok(Program {
+            imports: Vec::new(),
+            function_map,
+            tests: program.tests,
+            evaluation: program.evaluation,
+        })
+    }
+
+    /// Looks for `import_path` next to `importer` first, then in the search path.
+    fn resolve(&self, import_path: &str, importer: &Path) -> AnyResult<PathBuf> {
+        let import_path = Path::new(import_path);
+        if import_path.is_absolute() {
+            return Ok(import_path.to_owned());
+        }
+
+        let importer_dir = importer.parent().unwrap_or_else(|| Path::new(""));
+        std::iter::once(importer_dir)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(import_path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Cannot find `{}` imported from `{}`",
+                    import_path.display(),
+                    importer.display()
+                )
+            })
+    }
+}
+
+/// Line and column of the byte `offset` in `code`, both starting at 1.
+fn line_col(code: &str, offset: usize) -> (usize, usize) {
+    let before = &code[..offset];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Prefixes the names of all functions in `function_map` with `namespace`,
+/// along with every reference to them that is not shadowed by a local binding.
+pub fn qualify(function_map: FunctionMap, namespace: &str) -> FunctionMap {
+    let names: HashSet<String> = function_map.keys().cloned().collect();
+    let qualify_name = |name: &str| format!("{}.{}", namespace, name);
+
+    function_map
+        .into_iter()
+        .map(|(name, func)| {
+            let name = qualify_name(&name);
+            let variants = func
+                .variants
+                .into_iter()
+                .map(|var| {
+                    let locals: HashSet<String> = var
+                        .patterns
+                        .var_names()
+                        .into_iter()
+                        .map(String::from)
+                        .collect();
+                    let rename = |expr| rename_globals(expr, &names, &locals, &qualify_name);
+                    FunctionVariant {
+                        span: var.span,
+                        head: var.head,
+                        line: var.line,
+                        guard: var.guard.map(rename),
+                        body: rename(var.body),
+                        patterns: var.patterns,
+                    }
+                })
+                .collect();
+            (
+                name.clone(),
+                Function {
+                    name,
+                    variants,
+                    memoized: func.memoized,
+                    evaluation: func.evaluation,
+                    function_value: func.function_value,
+                },
+            )
+        })
+        .collect()
+}
+
+fn rename_globals(
+    expr: Expr,
+    globals: &HashSet<String>,
+    locals: &HashSet<String>,
+    rename: &dyn Fn(&str) -> String,
+) -> Expr {
+    let recurse = |expr| rename_globals(expr, globals, locals, rename);
+    match expr {
+        Expr::Variable {
+            name,
+            trampoline,
+            span,
+        } => {
+            let name = if globals.contains(&name) && !locals.contains(&name) {
+                rename(&name)
+            } else {
+                name
+            };
+            Expr::Variable {
+                name,
+                trampoline,
+                span,
+            }
+        }
+        Expr::Literal(_) => expr,
+        Expr::Call { callee, args } => Expr::Call {
+            callee: Box::new(recurse(*callee)),
+            args: args.into_iter().map(recurse).collect(),
+        },
+        Expr::Cat { children } => Expr::Cat {
+            children: children.into_iter().map(recurse).collect(),
+        },
+        Expr::Let {
+            pattern,
+            value,
+            body,
+        } => {
+            let mut body_locals = locals.clone();
+            body_locals.extend(pattern.var_names().into_iter().map(String::from));
+            Expr::Let {
+                value: Box::new(recurse(*value)),
+                body: Box::new(rename_globals(*body, globals, &body_locals, rename)),
+                pattern,
+            }
+        }
+    }
+}
@@ -1,18 +1,23 @@
 use crate::bindings::Bindings;
 use crate::bitstring::BitString;
 use crate::callable::Callable;
+use crate::integer::Order;
 use crate::value::Value;
 use crate::vm::{BasicExecResult, ExecError};
 
+pub type NativeFunctionPtr = fn(Vec<Value>) -> BasicExecResult<Value>;
+
 #[derive(Debug, Clone)]
 pub struct NativeFunction {
-    pub func: fn(Vec<Value>) -> BasicExecResult<Value>,
+    pub func: NativeFunctionPtr,
     pub name: String,
+    /// Whether calls have no effect besides returning a value. A memoized
+    /// call that makes an impure call, directly or not, is not recorded.
+    pub pure: bool,
 }
 
 pub fn make_bindings() -> Bindings {
-    let vec: Vec<(_, fn(Vec<Value>) -> BasicExecResult<Value>)> =
-        vec![("$", alloc_wrapper), ("?!", debug)];
+    let vec: Vec<(_, NativeFunctionPtr)> = vec![("$", alloc_wrapper), ("?!", debug)];
 
     Bindings::new(
         vec.into_iter()
@@ -20,7 +25,11 @@ pub fn make_bindings() -> Bindings {
                 let name = String::from(name_str);
                 (
                     name.clone(),
-                    Value::Callable(Callable::Native(NativeFunction { func, name })),
+                    Value::Callable(Callable::Native(NativeFunction {
+                        func,
+                        name,
+                        pure: false,
+                    })),
                 )
             })
             .collect(),
@@ -40,13 +49,10 @@ fn alloc(mut args: Vec<Value>) -> Option<Value> {
     }
 
     let bit_string = args.remove(0).into_bit_string()?;
-    let num_bytes = bit_string.as_usize();
+    let num_bytes: usize = bit_string.to_int(Order::default()).ok()?;
 
     // Safety: this code is unsafe. Good luck!
-    let encoded_ptr: usize = unsafe {
-        let ptr = libc::malloc(num_bytes);
-        std::mem::transmute(ptr)
-    };
+    let encoded_ptr = unsafe { libc::malloc(num_bytes) } as usize;
 
     Some(BitString::from_u64(encoded_ptr as u64).into())
 }
@@ -55,3 +61,26 @@ fn debug(args: Vec<Value>) -> BasicExecResult<Value> {
     println!("debug: {:?}", args);
     Ok(Value::BitString(BitString::empty()))
 }
+
+/// Natives are written as their name and linked again to the function of
+/// that name when read back.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NativeFunction {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NativeFunction {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match make_bindings().get_value(&name) {
+            Some(Value::Callable(Callable::Native(function))) => Ok(function.clone()),
+            _ => Err(serde::de::Error::custom(format!(
+                "Unknown native function `{}`",
+                name
+            ))),
+        }
+    }
+}
@@ -1,17 +1,22 @@
-use crate::ast::{Expr, Function, FunctionMap, FunctionVariant, Program};
-use crate::bitstring::Bit;
+use crate::ast::{
+    Evaluation, Expr, Function, FunctionMap, FunctionVariant, Import, Program, Span, Test,
+};
+use crate::bitstring::{Bit, BitString};
+use crate::literal;
 use crate::pattern::{
     ConstLenPattern, ConstLenPatternElement, MultiPattern, Pattern, VarLenPattern,
+    VarLenPatternSegment,
 };
 use anyhow::{Context, Result as AnyResult};
+use itertools::Itertools;
 use pest::Parser;
 use pest_derive::Parser;
 
 #[derive(Parser)]
 #[grammar = "bitmachine.pest"]
-struct BitMachineParser;
+pub(crate) struct BitMachineParser;
 
-type Pair<'a> = pest::iterators::Pair<'a, Rule>;
+pub(crate) type Pair<'a> = pest::iterators::Pair<'a, Rule>;
 
 macro_rules! assert_rule {
     ($var:ident :: $rule:ident) => {
@@ -22,6 +27,37 @@ macro_rules! assert_rule {
     };
 }
 
+/// Context of errors about a specific place in the source.
+#[derive(Debug)]
+pub struct SourceContext {
+    pub message: String,
+    pub span: Span,
+    line: usize,
+    column: usize,
+}
+
+impl SourceContext {
+    fn new(message: String, pair: &Pair<'_>) -> SourceContext {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        SourceContext {
+            message,
+            span: to_span(pair),
+            line,
+            column,
+        }
+    }
+}
+
+impl std::fmt::Display for SourceContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
 pub fn parse(code: &str) -> AnyResult<Program> {
     let toplevel = BitMachineParser::parse(Rule::toplevel, code)
         .map_err(anyhow::Error::from)
@@ -33,39 +69,258 @@ pub fn parse(code: &str) -> AnyResult<Program> {
     let program = toplevel.into_inner().next().unwrap();
     assert_rule!(::program);
 
+    let mut imports = Vec::new();
+    let mut memoized = Vec::new();
+    let mut evaluation = Evaluation::Eager;
+    let mut tests = Vec::new();
     let mut map = FunctionMap::new();
-    for (func_name, func_var) in program.into_inner().filter_map(parse_line) {
-        map.entry(func_name.clone())
-            .or_insert(Function {
-                name: func_name,
-                variants: vec![],
-            })
-            .variants
-            .push(func_var);
+    for line in program.into_inner() {
+        let (func_name, func_var, function_value) = match parse_line(line)? {
+            Some(Item::Import(import)) => {
+                imports.push(import);
+                continue;
+            }
+            Some(Item::Pragma(Pragma::Memo(names))) => {
+                memoized.extend(names);
+                continue;
+            }
+            Some(Item::Pragma(Pragma::Lazy)) => {
+                evaluation = Evaluation::Lazy;
+                continue;
+            }
+            Some(Item::Test(test)) => {
+                tests.push(test);
+                continue;
+            }
+            Some(Item::FuncDef(name, var, function_value)) => (name, var, function_value),
+            None => continue,
+        };
+        let function = map.entry(func_name.clone()).or_insert(Function {
+            name: func_name,
+            variants: vec![],
+            memoized: false,
+            evaluation: Evaluation::Eager,
+            function_value: false,
+        });
+        function.variants.push(func_var);
+        function.function_value |= function_value;
+    }
+
+    for (name, context) in memoized {
+        map.get_mut(&name).context(context)?.memoized = true;
     }
 
-    Ok(Program { function_map: map })
+    let mut program = Program {
+        imports,
+        function_map: map,
+        tests,
+        evaluation: Evaluation::Eager,
+    };
+    program.set_evaluation(evaluation);
+    Ok(program)
 }
 
-fn parse_line(line: Pair<'_>) -> Option<(String, FunctionVariant)> {
+/// Parses a single expression, such as one typed into the debugger.
+pub fn parse_expression(code: &str) -> AnyResult<Expr> {
+    let toplevel = BitMachineParser::parse(Rule::toplevel_expr, code)
+        .map_err(anyhow::Error::from)
+        .with_context(|| String::from("Parse error"))?
+        .next()
+        .unwrap();
+    assert_rule!(toplevel::toplevel_expr);
+    parse_expr(toplevel.into_inner().next().unwrap())
+}
+
+enum Item {
+    Import(Import),
+    Pragma(Pragma),
+    Test(Test),
+    /// Name, variant and whether it is marked as a function value.
+    FuncDef(String, FunctionVariant, bool),
+}
+
+fn parse_line(line: Pair<'_>) -> AnyResult<Option<Item>> {
     assert_rule!(::line);
     let inner = line.into_inner().next().unwrap();
     match inner.as_rule() {
-        Rule::empty_line => None,
-        Rule::func_def => Some(parse_func_def(inner)),
+        Rule::empty_line => Ok(None),
+        Rule::import => parse_import(inner).map(|x| Some(Item::Import(x))),
+        Rule::pragma => parse_pragma(inner).map(|x| Some(Item::Pragma(x))),
+        Rule::test_def => parse_test_def(inner).map(|x| Some(Item::Test(x))),
+        Rule::assert_def => parse_assert_def(inner).map(|x| Some(Item::Test(x))),
+        Rule::func_def => {
+            let (name, var, function_value) = parse_func_def(inner)?;
+            Ok(Some(Item::FuncDef(name, var, function_value)))
+        }
         _ => unreachable!(),
     }
 }
 
-fn parse_func_def(def: Pair<'_>) -> (String, FunctionVariant) {
-    assert_rule!(def::func_def);
+fn parse_import(import: Pair<'_>) -> AnyResult<Import> {
+    assert_rule!(::import);
+    let location = import.clone();
+    let mut iter = import.into_inner();
+    let path: String = iter
+        .next()
+        .unwrap()
+        .into_inner()
+        .map(|item| literal::unescape(item.as_str()))
+        .collect();
+
+    let namespace = match iter.next() {
+        Some(name) => String::from(parse_var_name(name)),
+        None => std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| is_var_name(stem))
+            .map(String::from)
+            .with_context(|| {
+                SourceContext::new(
+                    format!("Cannot derive a namespace from `{}`, use `as`", path),
+                    &location,
+                )
+            })?,
+    };
+
+    Ok(Import { path, namespace })
+}
+
+enum Pragma {
+    /// Functions named by `pragma memo`, each with the error to report if it
+    /// is not defined in the file.
+    Memo(Vec<(String, SourceContext)>),
+    /// `pragma lazy`, for the whole file.
+    Lazy,
+}
+
+fn parse_pragma(pragma: Pair<'_>) -> AnyResult<Pragma> {
+    assert_rule!(::pragma);
+    let mut iter = pragma.into_inner().peekable();
+    let kind = iter.next().unwrap();
+    let error = |message, pair| Err(anyhow::Error::msg(SourceContext::new(message, pair)));
+    match kind.as_str() {
+        "memo" if iter.peek().is_none() => {
+            error(String::from("`pragma memo` needs function names"), &kind)
+        }
+        "memo" => Ok(Pragma::Memo(
+            iter.map(|name| {
+                let message = format!("`{}` is not defined in this file", name.as_str());
+                let context = SourceContext::new(message, &name);
+                (String::from(parse_var_name(name)), context)
+            })
+            .collect(),
+        )),
+        "lazy" => match iter.next() {
+            Some(name) => error(String::from("`pragma lazy` takes no names"), &name),
+            None => Ok(Pragma::Lazy),
+        },
+        other => error(format!("Unknown pragma `{}`", other), &kind),
+    }
+}
+
+fn parse_test_def(def: Pair<'_>) -> AnyResult<Test> {
+    assert_rule!(def::test_def);
+    let line = def.as_span().start_pos().line_col().0;
     let mut iter = def.into_inner();
+    let name = iter
+        .next()
+        .unwrap()
+        .into_inner()
+        .map(|item| literal::unescape(item.as_str()))
+        .collect();
+    let actual = parse_expr(iter.next().unwrap())?;
+    let expected = match iter.next() {
+        Some(expected) => parse_expected(expected)?,
+        None => Expr::Literal("1".parse().unwrap()),
+    };
+    Ok(Test {
+        name,
+        line,
+        actual,
+        expected,
+    })
+}
+
+fn parse_assert_def(def: Pair<'_>) -> AnyResult<Test> {
+    assert_rule!(def::assert_def);
+    let line = def.as_span().start_pos().line_col().0;
+    let name = String::from(def.as_str()["assert".len()..].trim());
+    let mut iter = def.into_inner();
+    let actual = parse_expr(iter.next().unwrap())?;
+    let expected = parse_expected(iter.next().unwrap())?;
+    Ok(Test {
+        name,
+        line,
+        actual,
+        expected,
+    })
+}
+
+fn parse_expected(expected: Pair<'_>) -> AnyResult<Expr> {
+    assert_rule!(::expected);
+    parse_expr(expected.into_inner().next().unwrap())
+}
+
+/// Whether `name` can be used as a plain (non-operator) variable name.
+pub fn is_var_name(name: &str) -> bool {
+    BitMachineParser::parse(Rule::var_name, name)
+        .map(|mut pairs| pairs.next().unwrap().as_str() == name)
+        .unwrap_or(false)
+}
 
+fn parse_func_def(def: Pair<'_>) -> AnyResult<(String, FunctionVariant, bool)> {
+    assert_rule!(def::func_def);
+    let span = to_span(&def);
+    let line = def.as_span().start_pos().line_col().0;
+    let text = def.as_str();
+    let mut iter = def.into_inner().peekable();
+
+    let marker = iter.next_if(|x| x.as_rule() == Rule::function_value);
     let name = String::from(parse_var_name(iter.next().unwrap()));
-    let patterns = parse_patterns(iter.next().unwrap());
-    let body = parse_expr(iter.next().unwrap());
-    let var = FunctionVariant { patterns, body };
-    (name, var)
+    let patterns = iter.next().unwrap();
+    let mut head_end = patterns.as_span().end();
+    let patterns = parse_patterns(patterns)?;
+    if let (Some(marker), false) = (&marker, patterns.0.is_empty()) {
+        return Err(anyhow::Error::msg(SourceContext::new(
+            String::from("Only a definition without patterns can be marked with `@`"),
+            marker,
+        )));
+    }
+    let mut next = iter.next().unwrap();
+    let guard = if next.as_rule() == Rule::guard {
+        head_end = next.as_span().end();
+        let guard = parse_guard(next)?;
+        next = iter.next().unwrap();
+        Some(guard)
+    } else {
+        None
+    };
+    let head = Span {
+        start: span.start,
+        end: span.start + text[..head_end - span.start].trim_end().len(),
+    };
+    let body = parse_expr(next)?;
+    let var = FunctionVariant {
+        span,
+        head,
+        line,
+        patterns,
+        guard,
+        body,
+    };
+    Ok((name, var, marker.is_some()))
+}
+
+fn to_span(pair: &Pair<'_>) -> Span {
+    Span {
+        start: pair.as_span().start(),
+        end: pair.as_span().end(),
+    }
+}
+
+fn parse_guard(guard: Pair<'_>) -> AnyResult<Expr> {
+    assert_rule!(::guard);
+    parse_expr(guard.into_inner().next().unwrap())
 }
 
 fn parse_var_name(var_name: Pair<'_>) -> &str {
@@ -73,33 +328,54 @@ fn parse_var_name(var_name: Pair<'_>) -> &str {
     var_name.as_str()
 }
 
-fn parse_patterns(patterns: Pair<'_>) -> MultiPattern {
+fn parse_qualified_name(name: Pair<'_>) -> &str {
+    assert_rule!(name::qualified_name);
+    name.as_str()
+}
+
+fn parse_patterns(patterns: Pair<'_>) -> AnyResult<MultiPattern> {
     assert_rule!(::patterns);
-    MultiPattern(patterns.into_inner().map(parse_pattern).collect())
+    Ok(MultiPattern(
+        patterns.into_inner().map(parse_pattern).try_collect()?,
+    ))
 }
 
-fn parse_expr(expr: Pair<'_>) -> Expr {
+fn parse_expr(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(::expr);
     let inner = expr.into_inner().next().unwrap();
     match inner.as_rule() {
+        Rule::expr_let => parse_expr_let(inner),
         Rule::expr_call => parse_expr_call(inner),
         Rule::expr_single => parse_expr_single(inner),
         _ => unreachable!(),
     }
 }
 
-fn parse_expr_call(call: Pair<'_>) -> Expr {
+fn parse_expr_let(expr: Pair<'_>) -> AnyResult<Expr> {
+    assert_rule!(expr::expr_let);
+    let mut iter = expr.into_inner();
+    let pattern = parse_pattern(iter.next().unwrap())?;
+    let value = parse_expr(iter.next().unwrap())?;
+    let body = parse_expr(iter.next().unwrap())?;
+    Ok(Expr::Let {
+        pattern,
+        value: Box::new(value),
+        body: Box::new(body),
+    })
+}
+
+fn parse_expr_call(call: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(call::expr_call);
     let mut iter = call.into_inner().map(parse_expr_single);
-    let callee = iter.next().unwrap();
-    let args = iter.collect();
-    Expr::Call {
+    let callee = iter.next().unwrap()?;
+    let args = iter.try_collect()?;
+    Ok(Expr::Call {
         callee: Box::new(callee),
         args,
-    }
+    })
 }
 
-fn parse_expr_single(expr: Pair<'_>) -> Expr {
+fn parse_expr_single(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_single);
     let inner = expr.into_inner().next().unwrap();
     match inner.as_rule() {
@@ -109,14 +385,14 @@ fn parse_expr_single(expr: Pair<'_>) -> Expr {
     }
 }
 
-fn parse_expr_cat(expr: Pair<'_>) -> Expr {
+fn parse_expr_cat(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_cat);
-    Expr::Cat {
-        children: expr.into_inner().map(parse_expr_atomic).collect(),
-    }
+    Ok(Expr::Cat {
+        children: expr.into_inner().map(parse_expr_atomic).try_collect()?,
+    })
 }
 
-fn parse_expr_atomic(expr: Pair<'_>) -> Expr {
+fn parse_expr_atomic(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_atomic);
     let inner = expr.into_inner().next().unwrap();
     match inner.as_rule() {
@@ -127,98 +403,201 @@ fn parse_expr_atomic(expr: Pair<'_>) -> Expr {
     }
 }
 
-fn parse_expr_paren(expr: Pair<'_>) -> Expr {
+fn parse_expr_paren(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_paren);
     parse_expr(expr.into_inner().next().unwrap())
 }
 
-fn parse_expr_literal(expr: Pair<'_>) -> Expr {
+fn parse_expr_literal(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_literal);
-    Expr::Literal(expr.as_str().parse().unwrap())
+    let bit_string = match expr.clone().into_inner().next() {
+        Some(inner) => parse_literal(inner)?,
+        None => expr.as_str().parse().unwrap(),
+    };
+    Ok(Expr::Literal(bit_string))
+}
+
+fn parse_literal(lit: Pair<'_>) -> AnyResult<BitString> {
+    assert_rule!(lit::literal);
+    let location = lit.clone();
+    let text = lit.as_str();
+    let inner = lit.into_inner().next().unwrap();
+    let result = match inner.as_rule() {
+        Rule::literal_hex => parse_radix_literal(inner, 4, text),
+        Rule::literal_oct => parse_radix_literal(inner, 3, text),
+        Rule::literal_dec => {
+            let mut iter = inner.into_inner();
+            let bits = literal::from_decimal_digits(iter.next().unwrap().as_str());
+            parse_literal_width(iter.next().unwrap())
+                .and_then(|width| literal::fit_to_width(bits, width, text))
+        }
+        Rule::literal_char => literal::from_char(literal::unescape(
+            inner.into_inner().next().unwrap().as_str(),
+        )),
+        Rule::literal_string => {
+            let string: String = inner
+                .into_inner()
+                .map(|item| literal::unescape(item.as_str()))
+                .collect();
+            Ok(literal::from_bytes(string.as_bytes()))
+        }
+        _ => unreachable!(),
+    };
+    result.with_context(|| SourceContext::new(String::from("Invalid literal"), &location))
+}
+
+fn parse_radix_literal(
+    lit: Pair<'_>,
+    bits_per_digit: u32,
+    text: &str,
+) -> Result<BitString, literal::LiteralError> {
+    let mut iter = lit.into_inner();
+    let bits = literal::from_power_of_two_digits(iter.next().unwrap().as_str(), bits_per_digit);
+    match iter.next() {
+        Some(width) => literal::fit_to_width(bits, parse_literal_width(width)?, text),
+        None => Ok(bits),
+    }
 }
 
-fn parse_expr_name(expr: Pair<'_>) -> Expr {
+fn parse_literal_width(width: Pair<'_>) -> Result<usize, literal::LiteralError> {
+    assert_rule!(width::literal_width);
+    let digits = width.into_inner().next().unwrap().as_str();
+    digits
+        .parse()
+        .map_err(|_| literal::LiteralError::WidthTooLarge(String::from(digits)))
+}
+
+fn parse_expr_name(expr: Pair<'_>) -> AnyResult<Expr> {
     assert_rule!(expr::expr_name);
+    let span = to_span(&expr);
     let inner = expr.into_inner().next().unwrap();
-    match inner.as_rule() {
-        Rule::var_name => Expr::Variable {
-            name: String::from(parse_var_name(inner)),
+    Ok(match inner.as_rule() {
+        Rule::qualified_name => Expr::Variable {
+            name: String::from(parse_qualified_name(inner)),
             trampoline: true,
+            span,
         },
         Rule::var_name_no_trampoline => Expr::Variable {
-            name: String::from(parse_var_name(inner.into_inner().next().unwrap())),
+            name: String::from(parse_qualified_name(inner.into_inner().next().unwrap())),
             trampoline: false,
+            span,
+        },
+        Rule::var_name_call => Expr::Call {
+            callee: Box::new(Expr::Variable {
+                name: String::from(parse_qualified_name(inner.into_inner().next().unwrap())),
+                trampoline: false,
+                span,
+            }),
+            args: Vec::new(),
         },
         _ => unreachable!(),
-    }
+    })
 }
 
-fn parse_pattern(pattern: Pair<'_>) -> Pattern {
+fn parse_pattern(pattern: Pair<'_>) -> AnyResult<Pattern> {
     assert_rule!(::pattern);
     let inner = pattern.into_inner().next().unwrap();
     match inner.as_rule() {
         Rule::var_len_pattern => parse_var_len_pattern(inner),
-        Rule::const_len_pattern => parse_const_len_pattern(inner).into(),
-        Rule::empty_pattern => Pattern::empty(),
+        Rule::const_len_pattern => Ok(parse_const_len_pattern(inner)?.into()),
+        Rule::empty_pattern => Ok(Pattern::empty()),
         _ => unreachable!(),
     }
 }
 
-fn parse_var_len_pattern(pattern: Pair<'_>) -> Pattern {
+fn parse_var_len_pattern(pattern: Pair<'_>) -> AnyResult<Pattern> {
     assert_rule!(pattern::var_len_pattern);
 
-    let mut maybe_left = None;
-    let mut maybe_var_name = None;
-    let mut maybe_right = None;
-
-    for pair in pattern.into_inner() {
-        match (maybe_var_name.is_some(), pair.as_rule()) {
-            (false, Rule::const_len_pattern) => {
-                maybe_left = Some(parse_const_len_pattern(pair));
-            }
-            (true, Rule::const_len_pattern) => {
-                maybe_right = Some(parse_const_len_pattern(pair));
-            }
-            (false, Rule::var_name) => {
-                maybe_var_name = Some(parse_var_name(pair));
-            }
-            _ => unreachable!(),
-        }
-    }
+    let segments: Vec<_> = pattern
+        .into_inner()
+        .map(|pair| {
+            Ok(match pair.as_rule() {
+                Rule::const_len_pattern => {
+                    VarLenPatternSegment::Fixed(parse_const_len_pattern(pair)?)
+                }
+                Rule::var_name => VarLenPatternSegment::Variable {
+                    var_name: String::from(parse_var_name(pair)),
+                    min_len: 0,
+                    max_len: None,
+                },
+                Rule::pattern_range => parse_pattern_range(pair)?,
+                _ => unreachable!(),
+            })
+        })
+        .collect::<AnyResult<_>>()?;
 
-    let name = String::from(maybe_var_name.unwrap());
+    Ok(match segments.as_slice() {
+        [VarLenPatternSegment::Variable {
+            var_name,
+            min_len: 0,
+            max_len: None,
+        }] => Pattern::Anything {
+            name: var_name.clone(),
+        },
+        _ => VarLenPattern { segments }.into(),
+    })
+}
 
-    match (maybe_left, maybe_right) {
-        (None, None) => Pattern::Anything { name },
-        (maybe_left, maybe_right) => VarLenPattern {
-            left: maybe_left.unwrap_or(ConstLenPattern::empty()),
-            right: maybe_right.unwrap_or(ConstLenPattern::empty()),
-            bit_string_var_name: name,
-        }
-        .into(),
+fn parse_pattern_range(range: Pair<'_>) -> AnyResult<VarLenPatternSegment> {
+    assert_rule!(range::pattern_range);
+    let location = range.clone();
+    let mut iter = range.into_inner();
+    let var_name = String::from(parse_var_name(iter.next().unwrap()));
+    let parse_bound = |bound: Pair<'_>| match bound.as_str() {
+        "" => Ok(None),
+        digits => parse_length(digits, &bound).map(Some),
+    };
+    let min_len = parse_bound(iter.next().unwrap())?.unwrap_or(0);
+    let max_len = parse_bound(iter.next().unwrap())?;
+    if max_len.is_some_and(|max_len| max_len < min_len) {
+        return Err(anyhow::Error::msg(SourceContext::new(
+            format!("Range of `{}` matches no length", var_name),
+            &location,
+        )));
     }
+    Ok(VarLenPatternSegment::Variable {
+        var_name,
+        min_len,
+        max_len,
+    })
 }
 
-fn parse_const_len_pattern(pattern: Pair<'_>) -> ConstLenPattern {
+/// Number of bits given in a pattern.
+fn parse_length(digits: &str, location: &Pair<'_>) -> AnyResult<usize> {
+    digits.parse().map_err(|_| {
+        anyhow::Error::msg(SourceContext::new(
+            format!("Length `{}` is too large", digits),
+            location,
+        ))
+    })
+}
+
+fn parse_const_len_pattern(pattern: Pair<'_>) -> AnyResult<ConstLenPattern> {
     assert_rule!(pattern::const_len_pattern);
-    ConstLenPattern {
-        elements: pattern
-            .into_inner()
-            .map(parse_const_len_pattern_item)
-            .collect(),
-    }
+    let items: Vec<_> = pattern
+        .into_inner()
+        .map(parse_const_len_pattern_item)
+        .try_collect()?;
+    Ok(ConstLenPattern {
+        elements: items.into_iter().flatten().collect(),
+    })
 }
 
-fn parse_const_len_pattern_item(item: Pair<'_>) -> ConstLenPatternElement {
+fn parse_const_len_pattern_item(item: Pair<'_>) -> AnyResult<Vec<ConstLenPatternElement>> {
     assert_rule!(item::const_len_pattern_item);
     let inner = item.into_inner().next().unwrap();
-    match inner.as_rule() {
-        Rule::pattern_const => ConstLenPatternElement::ConstBit(parse_pattern_const(inner)),
-        Rule::pattern_bit => ConstLenPatternElement::AnyBit {
+    Ok(match inner.as_rule() {
+        Rule::literal => parse_literal(inner)?
+            .into_iter()
+            .map(ConstLenPatternElement::ConstBit)
+            .collect(),
+        Rule::pattern_const => vec![ConstLenPatternElement::ConstBit(parse_pattern_const(inner))],
+        Rule::pattern_bit => vec![ConstLenPatternElement::AnyBit {
             var_name: String::from(parse_pattern_bit(inner)),
-        },
+        }],
+        Rule::pattern_field => vec![parse_pattern_field(inner)?],
         _ => unreachable!(),
-    }
+    })
 }
 
 fn parse_pattern_const(pattern: Pair<'_>) -> Bit {
@@ -226,6 +605,15 @@ fn parse_pattern_const(pattern: Pair<'_>) -> Bit {
     pattern.as_str().parse().unwrap()
 }
 
+fn parse_pattern_field(field: Pair<'_>) -> AnyResult<ConstLenPatternElement> {
+    assert_rule!(field::pattern_field);
+    let mut iter = field.into_inner();
+    let var_name = String::from(parse_var_name(iter.next().unwrap()));
+    let width = iter.next().unwrap();
+    let width = parse_length(width.as_str(), &width)?;
+    Ok(ConstLenPatternElement::Field { var_name, width })
+}
+
 fn parse_pattern_bit(pattern: Pair<'_>) -> &str {
     assert_rule!(pattern::pattern_bit);
     parse_var_name(pattern.into_inner().next().unwrap())
@@ -2,7 +2,6 @@ use crate::bindings::Bindings;
 use crate::bitstring::{Bit, BitString};
 use crate::value::Value;
 use itertools::Itertools;
-use std::iter;
 
 pub trait PatternParseMulti {
     fn parse(&self, arg: Vec<Value>) -> Option<Bindings>;
@@ -13,23 +12,44 @@ pub trait PatternParse {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MultiPattern(pub Vec<Pattern>);
 
+impl MultiPattern {
+    pub fn var_names(&self) -> Vec<&str> {
+        self.0.iter().flat_map(Pattern::var_names).collect()
+    }
+
+    /// Whether matching looks at the bits of the argument at `index`: its
+    /// pattern is more than a name, or the name is bound again by another
+    /// pattern and the values have to be compared.
+    pub fn needs_bits(&self, index: usize) -> bool {
+        match self.0.get(index) {
+            Some(Pattern::Anything { name }) => {
+                self.var_names().iter().filter(|x| **x == name).count() > 1
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
 impl PatternParseMulti for MultiPattern {
     fn parse(&self, args: Vec<Value>) -> Option<Bindings> {
-        println!("Parse {:?} with {:?}", args, self);
         if args.len() != self.0.len() {
             return None;
         }
 
         args.into_iter()
             .zip_eq(self.0.iter())
-            .map(|(arg, pat)| pat.parse(arg))
-            .fold_options(Bindings::empty(), |b1, b2| b1.union_with(b2))
+            .try_fold(Bindings::empty(), |bindings, (arg, pat)| {
+                bindings.merge(pat.parse(arg)?)
+            })
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pattern {
     Anything { name: String },
     ConstLen(ConstLenPattern),
@@ -40,6 +60,28 @@ impl Pattern {
     pub fn empty() -> Pattern {
         Pattern::ConstLen(ConstLenPattern::empty())
     }
+
+    /// Whether matching looks at the bits of the argument, rather than only
+    /// binding it to a name.
+    pub fn needs_bits(&self) -> bool {
+        !matches!(self, Pattern::Anything { .. })
+    }
+
+    /// Names of all variables bound by this pattern, in order of appearance.
+    pub fn var_names(&self) -> Vec<&str> {
+        match self {
+            Pattern::Anything { name } => vec![name],
+            Pattern::ConstLen(pat) => pat.var_names(),
+            Pattern::VarLen(pat) => pat
+                .segments
+                .iter()
+                .flat_map(|segment| match segment {
+                    VarLenPatternSegment::Fixed(pat) => pat.var_names(),
+                    VarLenPatternSegment::Variable { var_name, .. } => vec![var_name.as_str()],
+                })
+                .collect(),
+        }
+    }
 }
 
 impl PatternParse for Pattern {
@@ -69,38 +111,56 @@ impl From<VarLenPattern> for Pattern {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstLenPattern {
     pub elements: Vec<ConstLenPatternElement>,
 }
 
 impl ConstLenPattern {
     pub fn len(&self) -> usize {
-        self.elements.len()
+        self.elements
+            .iter()
+            .map(ConstLenPatternElement::width)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     pub fn empty() -> ConstLenPattern {
         ConstLenPattern { elements: vec![] }
     }
+
+    pub fn var_names(&self) -> Vec<&str> {
+        self.elements
+            .iter()
+            .filter_map(|elem| match elem {
+                ConstLenPatternElement::ConstBit(_) => None,
+                ConstLenPatternElement::AnyBit { var_name }
+                | ConstLenPatternElement::Field { var_name, .. } => Some(var_name.as_str()),
+            })
+            .collect()
+    }
 }
 
 impl PatternParse for ConstLenPattern {
     fn parse(&self, arg: Value) -> Option<Bindings> {
         let bitstring = arg.into_bit_string()?;
-        if self.elements.len() != bitstring.len() {
+        if self.len() != bitstring.len() {
             return None;
         }
 
-        let iter = self
-            .elements
-            .iter()
-            .zip_eq(bitstring.iter())
-            .map(|(elem, bit)| elem.parse(bit));
-
         let mut bindings = Bindings::empty();
-        for item in iter {
-            if let Some((name, value)) = item? {
-                bindings.add(name, value.into());
+        let mut offset = 0;
+        for elem in &self.elements {
+            let len = elem.width();
+            if let Some((name, value)) = elem.parse(bitstring.slice(offset, offset + len))? {
+                if !bindings.add_consistent(name, value.into()) {
+                    return None;
+                }
             }
+            offset += len;
         }
 
         Some(bindings)
@@ -108,65 +168,141 @@ impl PatternParse for ConstLenPattern {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstLenPatternElement {
     ConstBit(Bit),
     AnyBit { var_name: String },
+    Field { var_name: String, width: usize },
 }
 
 impl ConstLenPatternElement {
-    pub fn parse(&self, arg: Bit) -> Option<Option<(String, BitString)>> {
+    pub fn width(&self) -> usize {
+        match self {
+            Self::ConstBit(_) | Self::AnyBit { .. } => 1,
+            Self::Field { width, .. } => *width,
+        }
+    }
+
+    /// Matches exactly `self.width()` bits.
+    pub fn parse(&self, arg: BitString) -> Option<Option<(String, BitString)>> {
         match self {
             Self::ConstBit(bit) => {
-                if arg == *bit {
+                if arg.bit_at(0) == Some(*bit) {
                     Some(None)
                 } else {
                     None
                 }
             }
-            Self::AnyBit { var_name } => {
-                let string = iter::once(arg).collect();
-                let tuple = (var_name.clone(), string);
+            Self::AnyBit { var_name } | Self::Field { var_name, .. } => {
+                let tuple = (var_name.clone(), arg);
                 Some(Some(tuple))
             }
         }
     }
 }
 
+/// A sequence of fixed-length and variable-length segments. Variable-length
+/// segments are matched leftmost-shortest: each one takes as few bits as
+/// possible for the rest of the pattern to still match.
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarLenPattern {
-    pub left: ConstLenPattern,
-    pub bit_string_var_name: String,
-    pub right: ConstLenPattern,
+    pub segments: Vec<VarLenPatternSegment>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VarLenPatternSegment {
+    Fixed(ConstLenPattern),
+    /// Matches between `min_len` and `max_len` bits, both inclusive.
+    Variable {
+        var_name: String,
+        min_len: usize,
+        max_len: Option<usize>,
+    },
+}
+
+impl VarLenPatternSegment {
+    fn min_len(&self) -> usize {
+        match self {
+            Self::Fixed(pat) => pat.len(),
+            Self::Variable { min_len, .. } => *min_len,
+        }
+    }
 }
 
 impl PatternParse for VarLenPattern {
     fn parse(&self, arg: Value) -> Option<Bindings> {
         let bitstring = arg.into_bit_string()?;
 
-        let left_len = self.left.len();
-        let right_len = self.right.len();
-        let total_len = bitstring.len();
-        let middle_len: usize = total_len.checked_sub(left_len + right_len)?;
-
-        let mut iter = bitstring.iter();
-        let left_str: BitString = iter.by_ref().take(left_len).collect();
-        let middle_str: BitString = iter.by_ref().take(middle_len).collect();
-        let right_str: BitString = iter.collect();
-
-        let left_parsed = self.left.parse(left_str.into())?;
-        let middle_parsed = self.parse_middle(middle_str.into());
-        let right_parsed = self.right.parse(right_str.into())?;
+        // min_lens[i] is the least number of bits segments[i..] can match.
+        let mut min_lens = vec![0; self.segments.len() + 1];
+        for (i, segment) in self.segments.iter().enumerate().rev() {
+            min_lens[i] = min_lens[i + 1] + segment.min_len();
+        }
 
-        Some(
-            left_parsed
-                .union_with(middle_parsed)
-                .union_with(right_parsed),
-        )
+        self.parse_from(&bitstring, 0, 0, &min_lens, Bindings::empty())
     }
 }
 
 impl VarLenPattern {
-    fn parse_middle(&self, middle_str: BitString) -> Bindings {
-        Bindings::new(iter::once((self.bit_string_var_name.clone(), middle_str.into())).collect())
+    fn parse_from(
+        &self,
+        bitstring: &BitString,
+        segment_index: usize,
+        offset: usize,
+        min_lens: &[usize],
+        bindings: Bindings,
+    ) -> Option<Bindings> {
+        let segment = match self.segments.get(segment_index) {
+            Some(x) => x,
+            None if offset == bitstring.len() => return Some(bindings),
+            None => return None,
+        };
+
+        let remaining = bitstring
+            .len()
+            .checked_sub(offset + min_lens[segment_index])?;
+        match segment {
+            VarLenPatternSegment::Fixed(pat) => {
+                let len = pat.len();
+                let parsed = pat.parse(bitstring.slice(offset, offset + len).into())?;
+                let bindings = bindings.merge(parsed)?;
+                self.parse_from(
+                    bitstring,
+                    segment_index + 1,
+                    offset + len,
+                    min_lens,
+                    bindings,
+                )
+            }
+            VarLenPatternSegment::Variable {
+                var_name,
+                min_len,
+                max_len,
+            } => {
+                let is_last_variable = self.segments[segment_index + 1..]
+                    .iter()
+                    .all(|x| matches!(x, VarLenPatternSegment::Fixed(_)));
+                let longest = min_len + remaining;
+                let shortest = if is_last_variable { longest } else { *min_len };
+                let longest = max_len.map_or(longest, |max_len| max_len.min(longest));
+
+                (shortest..=longest).find_map(|len| {
+                    let mut bindings = bindings.clone();
+                    let value = bitstring.slice(offset, offset + len).into();
+                    if !bindings.add_consistent(var_name.clone(), value) {
+                        return None;
+                    }
+                    self.parse_from(
+                        bitstring,
+                        segment_index + 1,
+                        offset + len,
+                        min_lens,
+                        bindings,
+                    )
+                })
+            }
+        }
     }
 }
@@ -0,0 +1,35 @@
+use crate::ast::Program;
+use crate::bindings::Bindings;
+use crate::module;
+use crate::parser;
+use crate::translator::Compile;
+
+pub const SOURCE: &str = include_str!("prelude.bm");
+
+/// Namespace the prelude functions use to refer to each other, so that user
+/// definitions shadowing them do not change their behaviour.
+pub const NAMESPACE: &str = "prelude";
+
+pub fn parse() -> Program {
+    parser::parse(SOURCE).expect("The prelude must be valid")
+}
+
+/// Bindings for every prelude function, both as `name` and `prelude.name`.
+pub fn make_bindings() -> Bindings {
+    let program = parse();
+    let compiled = Program {
+        imports: Vec::new(),
+        function_map: module::qualify(program.function_map, NAMESPACE),
+        tests: Vec::new(),
+        evaluation: program.evaluation,
+    }
+    .compile();
+
+    let mut bindings = Bindings::empty();
+    for (name, callable) in compiled.function_map {
+        let short_name = String::from(&name[NAMESPACE.len() + 1..]);
+        bindings.add(short_name, callable.clone().into());
+        bindings.add(name, callable.into());
+    }
+    bindings
+}
@@ -0,0 +1,224 @@
+//! Execution statistics collected by the VM when profiling is enabled.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+/// Index of a call path in `Profile::nodes`. Node 0 is the empty path.
+pub type NodeId = usize;
+
+pub const ROOT: NodeId = 0;
+
+#[derive(Debug, Default, Clone)]
+pub struct FunctionStats {
+    /// Calls of any kind, including tail calls.
+    pub calls: u64,
+    pub tail_calls: u64,
+    pub instructions: u64,
+    /// Attempts to match the arguments against the patterns of a variant.
+    pub match_attempts: u64,
+    pub match_failures: u64,
+    /// Bits of the strings built by `Cat` and by prepending and appending
+    /// to returned values.
+    pub bits_allocated: u64,
+    pub variants: BTreeMap<usize, VariantStats>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct VariantStats {
+    pub line: usize,
+    /// Times the body of the variant was run.
+    pub selected: u64,
+    pub match_attempts: u64,
+    pub match_failures: u64,
+}
+
+#[derive(Debug)]
+struct Node {
+    parent: NodeId,
+    function: String,
+    /// Instructions executed with exactly this call path.
+    instructions: u64,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    functions: HashMap<String, FunctionStats>,
+    nodes: Vec<Node>,
+    children: HashMap<(NodeId, String), NodeId>,
+}
+
+impl Default for Profile {
+    fn default() -> Profile {
+        Profile {
+            functions: HashMap::new(),
+            nodes: vec![Node {
+                parent: ROOT,
+                function: String::new(),
+                instructions: 0,
+            }],
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl Profile {
+    pub fn functions(&self) -> &HashMap<String, FunctionStats> {
+        &self.functions
+    }
+
+    fn function(&mut self, name: &str) -> &mut FunctionStats {
+        if !self.functions.contains_key(name) {
+            self.functions
+                .insert(String::from(name), FunctionStats::default());
+        }
+        self.functions.get_mut(name).unwrap()
+    }
+
+    /// Call path of `function` called from `parent`.
+    pub(crate) fn node(&mut self, parent: NodeId, function: &str) -> NodeId {
+        let key = (parent, String::from(function));
+        if let Some(&id) = self.children.get(&key) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            parent,
+            function: key.1.clone(),
+            instructions: 0,
+        });
+        self.children.insert(key, id);
+        id
+    }
+
+    pub(crate) fn record_instruction(&mut self, node: NodeId) {
+        self.nodes[node].instructions += 1;
+        let function = std::mem::take(&mut self.nodes[node].function);
+        self.function(&function).instructions += 1;
+        self.nodes[node].function = function;
+    }
+
+    pub(crate) fn record_call(&mut self, function: &str, tail: bool) {
+        let stats = self.function(function);
+        stats.calls += 1;
+        if tail {
+            stats.tail_calls += 1;
+        }
+    }
+
+    pub(crate) fn record_match(
+        &mut self,
+        function: &str,
+        variant: usize,
+        line: usize,
+        matched: bool,
+    ) {
+        let stats = self.function(function);
+        stats.match_attempts += 1;
+        let variant = stats.variants.entry(variant).or_default();
+        variant.line = line;
+        variant.match_attempts += 1;
+        if !matched {
+            stats.match_failures += 1;
+            variant.match_failures += 1;
+        }
+    }
+
+    pub(crate) fn record_selected(&mut self, function: &str, variant: usize) {
+        self.function(function)
+            .variants
+            .entry(variant)
+            .or_default()
+            .selected += 1;
+    }
+
+    pub(crate) fn record_bits(&mut self, function: &str, bits: usize) {
+        if bits > 0 {
+            self.function(function).bits_allocated += bits as u64;
+        }
+    }
+
+    /// Table of the functions sorted by the number of instructions they
+    /// executed, each followed by its variants.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| {
+            b.1.instructions
+                .cmp(&a.1.instructions)
+                .then(b.1.calls.cmp(&a.1.calls))
+                .then(a.0.cmp(b.0))
+        });
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:<24} {:>10} {:>10} {:>12} {:>10} {:>10} {:>12}",
+            "function", "calls", "tail", "instructions", "matches", "failures", "bits"
+        )
+        .unwrap();
+        for (name, stats) in &functions {
+            writeln!(
+                out,
+                "{:<24} {:>10} {:>10} {:>12} {:>10} {:>10} {:>12}",
+                name,
+                stats.calls,
+                stats.tail_calls,
+                stats.instructions,
+                stats.match_attempts,
+                stats.match_failures,
+                stats.bits_allocated
+            )
+            .unwrap();
+            for (index, variant) in &stats.variants {
+                let label = match variant.line {
+                    0 => format!("  variant {}", index),
+                    line => format!("  variant {} (line {})", index, line),
+                };
+                writeln!(
+                    out,
+                    "{:<24} {:>10} {:>10} {:>12} {:>10} {:>10}",
+                    label, variant.selected, "", "", variant.match_attempts, variant.match_failures
+                )
+                .unwrap();
+            }
+        }
+
+        let total = |f: fn(&FunctionStats) -> u64| functions.iter().map(|x| f(x.1)).sum::<u64>();
+        let calls = total(|x| x.calls);
+        let tail_calls = total(|x| x.tail_calls);
+        writeln!(
+            out,
+            "\n{} instructions, {} calls ({} regular, {} tail), {} match failures, {} bits allocated",
+            total(|x| x.instructions),
+            calls,
+            calls - tail_calls,
+            tail_calls,
+            total(|x| x.match_failures),
+            total(|x| x.bits_allocated)
+        )
+        .unwrap();
+        out
+    }
+
+    /// One `outer;inner count` line per call path, the format read by
+    /// flamegraph tools, with instructions as the count.
+    pub fn collapsed_stacks(&self) -> String {
+        let mut lines: Vec<String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.instructions > 0)
+            .map(|(id, node)| {
+                let mut path = Vec::new();
+                let mut current = id;
+                while current != ROOT {
+                    path.push(self.nodes[current].function.as_str());
+                    current = self.nodes[current].parent;
+                }
+                path.reverse();
+                format!("{} {}", path.join(";"), node.instructions)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
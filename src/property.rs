@@ -0,0 +1,320 @@
+//! Property-based testing of bitmachine functions against Rust oracles.
+//!
+//! A `Property` calls a function with random bit strings and compares each
+//! result with the one computed by a Rust closure. Failing inputs are
+//! shrunk before being reported.
+
+use crate::bindings::Bindings;
+use crate::bitstring::{Bit, BitString};
+use crate::callable::Callable;
+use crate::make_global_bindings;
+use crate::parser;
+use crate::prelude;
+use crate::translator::Compile;
+use crate::value::Value;
+use crate::vm::VM;
+use anyhow::Result as AnyResult;
+use std::fmt;
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+/// Upper bound on the number of successful shrinking steps.
+const MAX_SHRINKS: usize = 1000;
+
+pub struct Property {
+    function: String,
+    widths: Vec<RangeInclusive<usize>>,
+    cases: usize,
+    seed: u64,
+    step_limit: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum PropertyError {
+    #[error("No such function: `{0}`")]
+    FunctionNotFound(String),
+    #[error("No variant of `{function}` takes {arguments} arguments")]
+    WrongArity { function: String, arguments: usize },
+    #[error("Width range {start}..={end} of argument {index} is empty")]
+    EmptyWidths {
+        index: usize,
+        start: usize,
+        end: usize,
+    },
+    #[error("{0}")]
+    Counterexample(Counterexample),
+}
+
+#[derive(Debug)]
+pub struct Counterexample {
+    pub arguments: Vec<BitString>,
+    pub expected: BitString,
+    /// Value returned by the function, or the error it failed with.
+    pub actual: Result<Value, String>,
+    /// Seed that reproduces the original failure.
+    pub seed: u64,
+    /// Number of times the original failing input was shrunk.
+    pub shrinks: usize,
+}
+
+impl Property {
+    /// Property of the global function `function`. Arguments are added
+    /// with `arg`.
+    pub fn new(function: &str) -> Property {
+        Property {
+            function: String::from(function),
+            widths: Vec::new(),
+            cases: 100,
+            seed: 0,
+            step_limit: 1_000_000,
+        }
+    }
+
+    /// Adds an argument whose width is picked uniformly from `widths`.
+    pub fn arg(mut self, widths: RangeInclusive<usize>) -> Property {
+        self.widths.push(widths);
+        self
+    }
+
+    /// Number of random inputs to try.
+    pub fn cases(mut self, cases: usize) -> Property {
+        self.cases = cases;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Property {
+        self.seed = seed;
+        self
+    }
+
+    /// Maximum number of VM steps for a single call.
+    pub fn step_limit(mut self, step_limit: usize) -> Property {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Calls the function with `cases` random inputs and compares the
+    /// results with `oracle`. The first failure is shrunk and returned.
+    pub fn check<F>(&self, bindings: &Bindings, oracle: F) -> Result<(), PropertyError>
+    where
+        F: Fn(&[BitString]) -> BitString,
+    {
+        self.validate(bindings)?;
+
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..self.cases {
+            let arguments: Vec<BitString> = self
+                .widths
+                .iter()
+                .map(|widths| {
+                    let width = rng.in_range(widths);
+                    (0..width).map(|_| rng.bit()).collect()
+                })
+                .collect();
+
+            if let Some(failure) = self.try_case(bindings, &oracle, arguments) {
+                let failure = self.shrink(bindings, &oracle, failure);
+                return Err(PropertyError::Counterexample(failure));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every width range can be drawn from and that the
+    /// function can be called with as many arguments as there are ranges.
+    fn validate(&self, bindings: &Bindings) -> Result<(), PropertyError> {
+        for (index, widths) in self.widths.iter().enumerate() {
+            if widths.is_empty() {
+                return Err(PropertyError::EmptyWidths {
+                    index,
+                    start: *widths.start(),
+                    end: *widths.end(),
+                });
+            }
+        }
+
+        let callable = bindings
+            .get_value(&self.function)
+            .cloned()
+            .and_then(Value::into_callable)
+            .ok_or_else(|| PropertyError::FunctionNotFound(self.function.clone()))?;
+        // The arity of natives is not known.
+        if let Callable::Coded(function) = callable {
+            let arguments = self.widths.len();
+            if !function
+                .variants
+                .iter()
+                .any(|variant| variant.patterns.0.len() == arguments)
+            {
+                return Err(PropertyError::WrongArity {
+                    function: self.function.clone(),
+                    arguments,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a counterexample if the function disagrees with the oracle
+    /// on `arguments`.
+    fn try_case<F>(
+        &self,
+        bindings: &Bindings,
+        oracle: &F,
+        arguments: Vec<BitString>,
+    ) -> Option<Counterexample>
+    where
+        F: Fn(&[BitString]) -> BitString,
+    {
+        let expected = oracle(&arguments);
+        let actual = self.call(bindings, &arguments);
+        let passed = match &actual {
+            Ok(value) => *value == Value::from(expected.clone()),
+            Err(_) => false,
+        };
+
+        if passed {
+            None
+        } else {
+            Some(Counterexample {
+                arguments,
+                expected,
+                actual,
+                seed: self.seed,
+                shrinks: 0,
+            })
+        }
+    }
+
+    fn call(&self, bindings: &Bindings, arguments: &[BitString]) -> Result<Value, String> {
+        let mut vm = VM::new(bindings.clone());
+        vm.invoke_by_name(
+            &self.function,
+            arguments.iter().cloned().map(Value::from).collect(),
+        )
+        .and_then(|()| vm.run(Some(self.step_limit)))
+        .map_err(|e| e.to_string())
+    }
+
+    /// Repeatedly replaces the failure with the first smaller input that
+    /// still fails, until there is none.
+    fn shrink<F>(
+        &self,
+        bindings: &Bindings,
+        oracle: &F,
+        mut failure: Counterexample,
+    ) -> Counterexample
+    where
+        F: Fn(&[BitString]) -> BitString,
+    {
+        while failure.shrinks < MAX_SHRINKS {
+            let smaller = self
+                .shrink_candidates(&failure.arguments)
+                .into_iter()
+                .find_map(|candidate| self.try_case(bindings, oracle, candidate));
+            match smaller {
+                Some(smaller) => {
+                    failure = Counterexample {
+                        shrinks: failure.shrinks + 1,
+                        ..smaller
+                    }
+                }
+                None => break,
+            }
+        }
+        failure
+    }
+
+    /// Inputs that are one step smaller than `arguments`: an argument with
+    /// a bit dropped at either end or cut in half, as long as it stays
+    /// within its width range, or with a single one bit cleared.
+    fn shrink_candidates(&self, arguments: &[BitString]) -> Vec<Vec<BitString>> {
+        let mut candidates = Vec::new();
+        let mut with_arg = |i: usize, arg: BitString| {
+            let mut candidate = arguments.to_vec();
+            candidate[i] = arg;
+            candidates.push(candidate);
+        };
+
+        for (i, (arg, widths)) in arguments.iter().zip(&self.widths).enumerate() {
+            let len = arg.len();
+            if len / 2 >= *widths.start() && len / 2 < len {
+                with_arg(i, arg.slice(len - len / 2, len));
+                with_arg(i, arg.slice(0, len / 2));
+            }
+            if len > *widths.start() {
+                with_arg(i, arg.slice(1, len));
+                with_arg(i, arg.slice(0, len - 1));
+            }
+            for (j, bit) in arg.iter().enumerate() {
+                if bit == Bit::One {
+                    with_arg(
+                        i,
+                        arg.iter()
+                            .enumerate()
+                            .map(|(k, b)| if k == j { Bit::Zero } else { b })
+                            .collect(),
+                    );
+                }
+            }
+        }
+        candidates
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arguments: Vec<_> = self.arguments.iter().map(|arg| arg.to_string()).collect();
+        writeln!(f, "arguments: {}", arguments.join(" "))?;
+        writeln!(f, "expected:  {}", self.expected)?;
+        match &self.actual {
+            Ok(value) => writeln!(f, "actual:    {}", value)?,
+            Err(e) => writeln!(f, "error:     {}", e)?,
+        }
+        write!(f, "(seed {}, shrunk {} times)", self.seed, self.shrinks)
+    }
+}
+
+/// Global bindings for the program in `code`, with the prelude. Imports
+/// are not supported; load files through `ModuleLoader` to use them.
+pub fn bindings_from_source(code: &str) -> AnyResult<Bindings> {
+    let program = parser::parse(code)?.compile();
+    Ok(make_global_bindings(program, prelude::make_bindings()))
+}
+
+/// SplitMix64, which is plenty for generating test inputs and keeps the
+/// sequence for a given seed stable.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `range`, which must not be empty.
+    fn in_range(&mut self, range: &RangeInclusive<usize>) -> usize {
+        let offset = match ((range.end() - range.start()) as u64).checked_add(1) {
+            Some(span) => self.next() % span,
+            None => self.next(),
+        };
+        range.start() + offset as usize
+    }
+
+    fn bit(&mut self) -> Bit {
+        if self.next() >> 63 == 1 {
+            Bit::One
+        } else {
+            Bit::Zero
+        }
+    }
+}
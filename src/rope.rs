@@ -0,0 +1,94 @@
+//! Bits accumulated by tail calls until the value they surround is returned.
+
+use crate::bitstring::BitString;
+use std::sync::Arc;
+
+/// End of a rope that pieces are added to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Front,
+    Back,
+}
+
+/// A bit string built up by adding pieces at one end. The pieces added
+/// before are shared rather than copied, so adding a piece and cloning take
+/// constant time; the bits are only copied when the rope is materialized.
+#[derive(Debug, Clone)]
+pub struct BitRope {
+    newest: Option<Arc<Piece>>,
+    len: usize,
+    end: End,
+}
+
+#[derive(Debug)]
+struct Piece {
+    bits: BitString,
+    older: Option<Arc<Piece>>,
+}
+
+impl BitRope {
+    pub fn new(end: End) -> BitRope {
+        BitRope {
+            newest: None,
+            len: 0,
+            end,
+        }
+    }
+
+    pub fn from_bit_string(bits: BitString, end: End) -> BitRope {
+        let mut rope = BitRope::new(end);
+        rope.push(bits);
+        rope
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `bits` at the end the rope grows at.
+    pub fn push(&mut self, bits: BitString) {
+        if bits.is_empty() {
+            return;
+        }
+        self.len += bits.len();
+        self.newest = Some(Arc::new(Piece {
+            bits,
+            older: self.newest.take(),
+        }));
+    }
+
+    /// The pieces from first to last.
+    pub fn pieces(&self) -> Vec<&BitString> {
+        let mut pieces = Vec::new();
+        let mut current = self.newest.as_deref();
+        while let Some(piece) = current {
+            pieces.push(&piece.bits);
+            current = piece.older.as_deref();
+        }
+        if self.end == End::Back {
+            pieces.reverse();
+        }
+        pieces
+    }
+
+    pub fn to_bit_string(&self) -> BitString {
+        BitString::concat_all(self.pieces())
+    }
+}
+
+impl Drop for BitRope {
+    /// Drops long chains of pieces one at a time rather than recursively.
+    fn drop(&mut self) {
+        let mut current = self.newest.take();
+        while let Some(piece) = current {
+            current = match Arc::try_unwrap(piece) {
+                Ok(mut piece) => piece.older.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
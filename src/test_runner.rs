@@ -0,0 +1,309 @@
+use crate::ast::Evaluation;
+use crate::bindings::Bindings;
+use crate::bitstring::BitString;
+use crate::coded_function::CodedFunction;
+use crate::compiled::{Program as CompiledProgram, Test};
+use crate::make_global_bindings;
+use crate::module::{self, ModuleLoader};
+use crate::translator::Compile;
+use crate::value::Value;
+use crate::vm::{BasicExecResult, VM};
+use anyhow::{Context, Result as AnyResult};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub struct TestOptions {
+    /// Maximum number of VM steps to evaluate either side of a test.
+    pub step_limit: usize,
+    pub json_report: Option<PathBuf>,
+    pub junit_report: Option<PathBuf>,
+    /// Whether calls are lazy in every file, whatever the file asks for.
+    pub lazy: bool,
+}
+
+impl Default for TestOptions {
+    fn default() -> TestOptions {
+        TestOptions {
+            step_limit: 1_000_000,
+            json_report: None,
+            junit_report: None,
+            lazy: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Passed,
+    Failed { expected: String, actual: String },
+    Error(String),
+}
+
+#[derive(Debug)]
+struct TestResult {
+    file: PathBuf,
+    name: String,
+    line: usize,
+    outcome: Outcome,
+    duration: Duration,
+}
+
+/// Runs every test found in `paths`, which may be files or directories to
+/// search for `.bm` files. Returns whether all of them passed.
+pub fn run_tests(
+    paths: &[PathBuf],
+    loader: &mut ModuleLoader,
+    prelude_bindings: Bindings,
+    options: &TestOptions,
+) -> AnyResult<bool> {
+    let files = module::find_sources(paths)?;
+
+    let mut results = Vec::new();
+    for file in files {
+        run_file(&file, loader, &prelude_bindings, options, &mut results);
+    }
+
+    let failures: Vec<_> = results
+        .iter()
+        .filter(|result| !matches!(result.outcome, Outcome::Passed))
+        .collect();
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for result in &failures {
+            println!(
+                "  {}:{} {:?}",
+                result.file.display(),
+                result.line,
+                result.name
+            );
+            match &result.outcome {
+                Outcome::Passed => (),
+                Outcome::Failed { expected, actual } => {
+                    println!("    expected: {}", expected);
+                    println!("    actual:   {}", actual);
+                }
+                Outcome::Error(message) => println!("    error: {}", message),
+            }
+        }
+    }
+
+    let passed = results.len() - failures.len();
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len()
+    );
+
+    if let Some(path) = &options.json_report {
+        std::fs::write(path, json_report(&results))
+            .with_context(|| format!("Cannot write `{}`", path.display()))?;
+    }
+    if let Some(path) = &options.junit_report {
+        std::fs::write(path, junit_report(&results))
+            .with_context(|| format!("Cannot write `{}`", path.display()))?;
+    }
+
+    Ok(failures.is_empty())
+}
+
+fn run_file(
+    file: &Path,
+    loader: &mut ModuleLoader,
+    prelude_bindings: &Bindings,
+    options: &TestOptions,
+    results: &mut Vec<TestResult>,
+) {
+    let program = match loader.load(file) {
+        Ok(mut x) => {
+            if options.lazy {
+                x.set_evaluation(Evaluation::Lazy);
+            }
+            x.compile()
+        }
+        Err(e) => {
+            println!("test {} ... ERROR", file.display());
+            results.push(TestResult {
+                file: file.to_owned(),
+                name: String::from("(load)"),
+                line: 0,
+                outcome: Outcome::Error(format!("{:#}", e)),
+                duration: Duration::default(),
+            });
+            return;
+        }
+    };
+
+    let tests = program.tests;
+    let bindings = make_global_bindings(
+        CompiledProgram {
+            function_map: program.function_map,
+            tests: Vec::new(),
+        },
+        prelude_bindings.clone(),
+    );
+
+    for test in tests {
+        let start = Instant::now();
+        let outcome = run_test(&test, &bindings, options.step_limit);
+        let duration = start.elapsed();
+        println!(
+            "test {}:{} {:?} ... {}",
+            file.display(),
+            test.line,
+            test.name,
+            match outcome {
+                Outcome::Passed => "ok",
+                Outcome::Failed { .. } => "FAILED",
+                Outcome::Error(_) => "ERROR",
+            }
+        );
+        results.push(TestResult {
+            file: file.to_owned(),
+            name: test.name,
+            line: test.line,
+            outcome,
+            duration,
+        });
+    }
+}
+
+fn run_test(test: &Test, bindings: &Bindings, step_limit: usize) -> Outcome {
+    let evaluate = |function: &CodedFunction| {
+        evaluate(function.clone(), bindings.clone(), step_limit).map_err(|e| e.to_string())
+    };
+
+    let (actual, expected) = match (evaluate(&test.actual), evaluate(&test.expected)) {
+        (Ok(actual), Ok(expected)) => (actual, expected),
+        (Err(e), _) => return Outcome::Error(e),
+        (_, Err(e)) => return Outcome::Error(format!("in the expected value: {}", e)),
+    };
+
+    if actual == expected {
+        Outcome::Passed
+    } else {
+        Outcome::Failed {
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+    }
+}
+
+/// Evaluates a function of no arguments in a fresh VM.
+fn evaluate(
+    function: CodedFunction,
+    bindings: Bindings,
+    step_limit: usize,
+) -> BasicExecResult<Value> {
+    let mut vm = VM::new(bindings);
+    vm.invoke(
+        function.into(),
+        Vec::new(),
+        BitString::empty(),
+        BitString::empty(),
+    )?;
+    vm.run(Some(step_limit))
+}
+
+fn json_report(results: &[TestResult]) -> String {
+    let tests: Vec<_> = results
+        .iter()
+        .map(|result| {
+            let mut test = json!({
+                "file": result.file.display().to_string(),
+                "name": result.name,
+                "line": result.line,
+                "duration_ms": result.duration.as_millis() as u64,
+            });
+            match &result.outcome {
+                Outcome::Passed => test["status"] = json!("passed"),
+                Outcome::Failed { expected, actual } => {
+                    test["status"] = json!("failed");
+                    test["expected"] = json!(expected);
+                    test["actual"] = json!(actual);
+                }
+                Outcome::Error(message) => {
+                    test["status"] = json!("error");
+                    test["error"] = json!(message);
+                }
+            }
+            test
+        })
+        .collect();
+    let passed = results
+        .iter()
+        .filter(|x| matches!(x.outcome, Outcome::Passed))
+        .count();
+
+    let report = json!({
+        "passed": passed,
+        "failed": results.len() - passed,
+        "tests": tests,
+    });
+    let mut out = report.to_string();
+    out.push('\n');
+    out
+}
+
+fn junit_report(results: &[TestResult]) -> String {
+    let count = |f: fn(&Outcome) -> bool| results.iter().filter(|x| f(&x.outcome)).count();
+    let failures = count(|x| matches!(x, Outcome::Failed { .. }));
+    let errors = count(|x| matches!(x, Outcome::Error(_)));
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        out,
+        "<testsuite name=\"bitmachine\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        results.len(),
+        failures,
+        errors
+    )
+    .unwrap();
+    for result in results {
+        write!(
+            out,
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.file.display().to_string()),
+            xml_escape(&result.name),
+            result.duration.as_secs_f64()
+        )
+        .unwrap();
+        match &result.outcome {
+            Outcome::Passed => out.push_str("/>\n"),
+            Outcome::Failed { expected, actual } => writeln!(
+                out,
+                ">\n    <failure message=\"expected {}, got {}\"/>\n  </testcase>",
+                xml_escape(expected),
+                xml_escape(actual)
+            )
+            .unwrap(),
+            Outcome::Error(message) => writeln!(
+                out,
+                ">\n    <error message=\"{}\"/>\n  </testcase>",
+                xml_escape(message)
+            )
+            .unwrap(),
+        }
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => write!(out, "&#{};", c as u32).unwrap(),
+            // Not allowed anywhere in XML 1.0, not even as references.
+            '\0'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => out.push(char::REPLACEMENT_CHARACTER),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,97 @@
+use crate::bindings::Bindings;
+use crate::bytecode::Bytecode;
+use crate::value::Value;
+use crate::vm::ExecError;
+use std::sync::{Arc, Mutex};
+
+/// Argument of a call made in lazy mode, evaluated the first time its bits
+/// are needed. Clones share the evaluation, so it happens at most once.
+#[derive(Clone)]
+pub struct Thunk(Arc<Mutex<State>>);
+
+enum State {
+    Delayed(Delayed),
+    Running,
+    Evaluated(Value),
+    /// The evaluation stopped with the error with this message.
+    Failed(String),
+}
+
+/// Code of a delayed argument, with the scope it was written in.
+pub(crate) struct Delayed {
+    pub function: String,
+    pub variant: usize,
+    pub line: usize,
+    pub bytecode: Bytecode,
+    pub bindings: Bindings,
+}
+
+impl Thunk {
+    pub(crate) fn new(delayed: Delayed) -> Thunk {
+        Thunk(Arc::new(Mutex::new(State::Delayed(delayed))))
+    }
+
+    /// The value of the argument, once it is evaluated.
+    pub fn value(&self) -> Option<Value> {
+        match &*self.0.lock().unwrap() {
+            State::Evaluated(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_evaluated(&self) -> bool {
+        matches!(*self.0.lock().unwrap(), State::Evaluated(_))
+    }
+
+    /// Whether both are clones of the same thunk.
+    pub fn ptr_eq(&self, other: &Thunk) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Takes the code to evaluate. Fails if the evaluation has already
+    /// started, or if it has failed before.
+    pub(crate) fn start(&self) -> Result<Delayed, ExecError> {
+        let mut state = self.0.lock().unwrap();
+        match std::mem::replace(&mut *state, State::Running) {
+            State::Delayed(delayed) => Ok(delayed),
+            State::Failed(message) => {
+                *state = State::Failed(message.clone());
+                Err(ExecError::ThunkFailed { message })
+            }
+            other => {
+                *state = other;
+                Err(ExecError::ThunkReentered)
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self, value: Value) {
+        *self.0.lock().unwrap() = State::Evaluated(value);
+    }
+
+    /// Records that the evaluation stopped with `error`, if it was running.
+    pub(crate) fn fail(&self, error: &ExecError) {
+        let mut state = self.0.lock().unwrap();
+        if let State::Running = *state {
+            *state = State::Failed(error.to_string());
+        }
+    }
+}
+
+impl std::fmt::Debug for Thunk {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.value() {
+            Some(value) => std::fmt::Debug::fmt(&value, fmt),
+            None => fmt.write_str("<thunk>"),
+        }
+    }
+}
+
+impl std::hash::Hash for Thunk {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match self.value() {
+            Some(value) => value.hash(state),
+            None => Arc::as_ptr(&self.0).hash(state),
+        }
+    }
+}
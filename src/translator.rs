@@ -1,7 +1,10 @@
-use crate::ast::{Expr, Function, FunctionMap, FunctionVariant, Program};
+use crate::ast::{Evaluation, Expr, Function, FunctionMap, FunctionVariant, Program, Test};
 use crate::bytecode::{Bytecode, Instruction};
 use crate::coded_function::{CodedFunction, CodedFunctionVariant};
-use crate::compiled::{FunctionMap as CompiledFunctionMap, Program as CompiledProgram};
+use crate::compiled::{
+    FunctionMap as CompiledFunctionMap, Program as CompiledProgram, Test as CompiledTest,
+};
+use crate::pattern::{MultiPattern, Pattern};
 use std::iter;
 
 pub trait Compile {
@@ -9,26 +12,35 @@ pub trait Compile {
 }
 
 pub trait ToBytecode {
-    fn to_bytecode(self) -> Bytecode;
+    fn to_bytecode(self, evaluation: Evaluation) -> Bytecode;
 }
 
 pub trait ToInstructions {
-    fn to_instructions(self, call_status: CallStatus) -> Vec<Instruction>;
+    fn to_instructions(self, call_status: CallStatus, evaluation: Evaluation) -> Vec<Instruction>;
 }
 
 impl<T: ToInstructions> ToBytecode for T {
-    fn to_bytecode(self) -> Bytecode {
-        Bytecode::new(self.to_instructions(CallStatus::Tail {
-            prepend: 0,
-            append: 0,
-        }))
+    fn to_bytecode(self, evaluation: Evaluation) -> Bytecode {
+        Bytecode::new(self.to_instructions(
+            CallStatus::Tail {
+                prepend: 0,
+                append: 0,
+            },
+            evaluation,
+        ))
     }
 }
 
 impl Compile for Program {
     fn compile(self) -> CompiledProgram {
+        let evaluation = self.evaluation;
         CompiledProgram {
             function_map: compile_function_map(self.function_map),
+            tests: self
+                .tests
+                .into_iter()
+                .map(|test| compile_test(test, evaluation))
+                .collect(),
         }
     }
 }
@@ -41,20 +53,57 @@ fn compile_function_map(function_map: FunctionMap) -> CompiledFunctionMap {
 }
 
 fn compile_function(func: Function) -> CodedFunction {
+    let evaluation = func.evaluation;
     CodedFunction {
         name: func.name,
         variants: func
             .variants
             .into_iter()
-            .map(compile_function_variant)
+            .map(|var| compile_function_variant(var, evaluation))
             .collect(),
+        memoized: func.memoized,
+        function_value: func.function_value,
     }
 }
 
-fn compile_function_variant(var: FunctionVariant) -> CodedFunctionVariant {
+fn compile_function_variant(var: FunctionVariant, evaluation: Evaluation) -> CodedFunctionVariant {
     CodedFunctionVariant {
+        line: var.line,
         patterns: var.patterns,
-        body: var.body.to_bytecode(),
+        // The guard must not tail-call: its task has to survive until the
+        // result is known, so that the variant selection can be resumed.
+        guard: var
+            .guard
+            .map(|guard| Bytecode::new(guard.to_instructions(CallStatus::Regular, evaluation))),
+        body: var.body.to_bytecode(evaluation),
+    }
+}
+
+fn compile_test(test: Test, evaluation: Evaluation) -> CompiledTest {
+    CompiledTest {
+        actual: compile_constant(format!("test {:?}", test.name), test.actual, evaluation),
+        expected: compile_constant(
+            format!("expected value of test {:?}", test.name),
+            test.expected,
+            evaluation,
+        ),
+        name: test.name,
+        line: test.line,
+    }
+}
+
+/// A function of no arguments evaluating `expr`.
+pub fn compile_constant(name: String, expr: Expr, evaluation: Evaluation) -> CodedFunction {
+    CodedFunction {
+        name,
+        variants: vec![CodedFunctionVariant {
+            line: 0,
+            patterns: MultiPattern(Vec::new()),
+            guard: None,
+            body: expr.to_bytecode(evaluation),
+        }],
+        memoized: false,
+        function_value: false,
     }
 }
 
@@ -65,9 +114,11 @@ pub enum CallStatus {
 }
 
 impl ToInstructions for Expr {
-    fn to_instructions(self, call_status: CallStatus) -> Vec<Instruction> {
+    fn to_instructions(self, call_status: CallStatus, evaluation: Evaluation) -> Vec<Instruction> {
         match self {
-            Expr::Variable { name, trampoline } => {
+            Expr::Variable {
+                name, trampoline, ..
+            } => {
                 if trampoline {
                     vec![Instruction::LoadVar { name }, Instruction::Trampoline]
                 } else {
@@ -76,24 +127,66 @@ impl ToInstructions for Expr {
             }
             Expr::Literal(bit_string) => vec![Instruction::LoadConst(bit_string)],
             Expr::Call { callee, args } => {
-                function_call_to_instructions(*callee, args, call_status)
+                function_call_to_instructions(*callee, args, call_status, evaluation)
+            }
+            Expr::Cat { children } => {
+                concatenation_to_instructions(children, call_status, evaluation)
             }
-            Expr::Cat { children } => concatenation_to_instructions(children, call_status),
+            Expr::Let {
+                pattern,
+                value,
+                body,
+            } => let_to_instructions(pattern, *value, *body, call_status, evaluation),
         }
     }
 }
 
+fn let_to_instructions(
+    pattern: Pattern,
+    value: Expr,
+    body: Expr,
+    call_status: CallStatus,
+    evaluation: Evaluation,
+) -> Vec<Instruction> {
+    let mut instructions = value.to_instructions(CallStatus::Regular, evaluation);
+    instructions.push(Instruction::Bind(pattern));
+    instructions.append(&mut body.to_instructions(call_status, evaluation));
+
+    // A tail call ends the task, so there is nothing left to restore.
+    if !instructions.last().is_some_and(Instruction::is_tail) {
+        instructions.push(Instruction::Unbind);
+    }
+
+    instructions
+}
+
 fn function_call_to_instructions(
     callee: Expr,
     args: Vec<Expr>,
     call_status: CallStatus,
+    evaluation: Evaluation,
 ) -> Vec<Instruction> {
-    let mut instructions = callee.to_instructions(CallStatus::Regular);
+    let mut instructions = callee.to_instructions(CallStatus::Regular, evaluation);
 
     let len = args.len();
 
     for arg in args.into_iter() {
-        instructions.append(&mut arg.to_instructions(CallStatus::Regular));
+        let delayed = evaluation == Evaluation::Lazy
+            && !matches!(
+                arg,
+                Expr::Literal(_)
+                    | Expr::Variable {
+                        trampoline: false,
+                        ..
+                    }
+            );
+        let mut arg_instructions = arg.to_instructions(CallStatus::Regular, evaluation);
+        if delayed {
+            // Like a guard, the thunk must not tail-call: its task stores the value.
+            instructions.push(Instruction::Delay(Bytecode::new(arg_instructions)));
+        } else {
+            instructions.append(&mut arg_instructions);
+        }
     }
 
     instructions.push(match call_status {
@@ -104,8 +197,12 @@ fn function_call_to_instructions(
     instructions
 }
 
-fn concatenation_to_instructions(children: Vec<Expr>, call_status: CallStatus) -> Vec<Instruction> {
-    match (find_single_complex_expr(&children), call_status) {
+fn concatenation_to_instructions(
+    children: Vec<Expr>,
+    call_status: CallStatus,
+    evaluation: Evaluation,
+) -> Vec<Instruction> {
+    match (find_last_complex_expr(&children), call_status) {
         (Some(i), CallStatus::Tail { prepend, append }) => {
             let (prepends, focus, appends) = split_off_3(children, i);
             let prepends_len = prepends.len();
@@ -113,13 +210,15 @@ fn concatenation_to_instructions(children: Vec<Expr>, call_status: CallStatus) -
 
             prepends
                 .into_iter()
-                .chain(appends.into_iter())
-                .map(|expr| expr.to_instructions(CallStatus::Regular))
-                .flatten()
-                .chain(focus.to_instructions(CallStatus::Tail {
-                    prepend: prepends_len + prepend,
-                    append: appends_len + append,
-                }))
+                .chain(appends)
+                .flat_map(|expr| expr.to_instructions(CallStatus::Regular, evaluation))
+                .chain(focus.to_instructions(
+                    CallStatus::Tail {
+                        prepend: prepends_len + prepend,
+                        append: appends_len + append,
+                    },
+                    evaluation,
+                ))
                 .collect()
         }
         _ => {
@@ -127,8 +226,7 @@ fn concatenation_to_instructions(children: Vec<Expr>, call_status: CallStatus) -
 
             children
                 .into_iter()
-                .map(|expr| expr.to_instructions(CallStatus::Regular))
-                .flatten()
+                .flat_map(|expr| expr.to_instructions(CallStatus::Regular, evaluation))
                 .chain(iter::once(Instruction::Cat(children_len)))
                 .collect()
         }
@@ -151,25 +249,13 @@ trait ExprClassification {
 
 impl ExprClassification for Expr {
     fn is_complex(&self) -> bool {
-        match self {
-            Expr::Call { .. } => true,
-            _ => false,
-        }
+        matches!(self, Expr::Call { .. })
     }
 }
 
-fn find_single_complex_expr(exprs: &[Expr]) -> Option<usize> {
-    let mut last_complex = None;
-
-    for (i, expr) in exprs.iter().enumerate() {
-        match (last_complex, expr.is_complex()) {
-            (_, false) => (),
-            (None, true) => {
-                last_complex = Some(i);
-            }
-            (Some(_), true) => return None,
-        }
-    }
-
-    last_complex
+/// The last call among `exprs`, which can be a tail call: the values of
+/// the expressions around it, including earlier calls, are evaluated first
+/// and added by the call on either side of its value.
+fn find_last_complex_expr(exprs: &[Expr]) -> Option<usize> {
+    exprs.iter().rposition(Expr::is_complex)
 }
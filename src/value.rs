@@ -1,26 +1,48 @@
 use crate::bitstring::BitString;
 use crate::callable::Callable;
+use crate::thunk::Thunk;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     BitString(BitString),
     Callable(Callable),
+    /// Delayed argument of a lazy call. Never serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Thunk(Thunk),
 }
 
 impl Value {
     pub fn into_bit_string(self) -> Option<BitString> {
-        match self {
+        match self.resolve() {
             Value::BitString(s) => Some(s),
             _ => None,
         }
     }
 
     pub fn into_callable(self) -> Option<Callable> {
-        match self {
+        match self.resolve() {
             Value::Callable(c) => Some(c),
             _ => None,
         }
     }
+
+    /// The value of an evaluated thunk, or `self` for anything else.
+    pub fn resolve(self) -> Value {
+        match self {
+            Value::Thunk(thunk) => thunk.value().unwrap_or(Value::Thunk(thunk)),
+            value => value,
+        }
+    }
+
+    /// The thunk, if this is one that is not evaluated yet.
+    pub fn unevaluated(&self) -> Option<&Thunk> {
+        match self {
+            Value::Thunk(thunk) if !thunk.is_evaluated() => Some(thunk),
+            _ => None,
+        }
+    }
 }
 
 impl From<BitString> for Value {
@@ -34,3 +56,51 @@ impl From<Callable> for Value {
         Value::Callable(c)
     }
 }
+
+/// Bit strings are the same if their bits are; callables are the same if
+/// they refer to the same function. Evaluated thunks compare as their value,
+/// the others are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::BitString(a), Value::BitString(b)) => a == b,
+            (Value::Callable(a), Value::Callable(b)) => a.name() == b.name(),
+            (Value::Thunk(a), Value::Thunk(b)) if a.ptr_eq(b) => true,
+            (Value::Thunk(a), b) => a.value().is_some_and(|a| a == *b),
+            (a, Value::Thunk(b)) => b.value().is_some_and(|b| *a == b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Value::Thunk(thunk) = self {
+            return thunk.hash(state);
+        }
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::BitString(s) => s.hash(state),
+            Value::Callable(c) => c.name().hash(state),
+            Value::Thunk(_) => unreachable!(),
+        }
+    }
+}
+
+/// A value as written in source: bits, `.` for the empty string, or the
+/// name of a function in angle brackets. A thunk is shown as its value, or
+/// as `<thunk>` until it is evaluated.
+impl std::fmt::Display for Value {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::BitString(s) => std::fmt::Display::fmt(s, fmt),
+            Value::Callable(c) => fmt.pad(&format!("<function {}>", c.name())),
+            Value::Thunk(thunk) => match thunk.value() {
+                Some(value) => std::fmt::Display::fmt(&value, fmt),
+                None => fmt.pad("<thunk>"),
+            },
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use crate::bindings::Bindings;
-use crate::bitstring::BitString;
+use crate::bitstring::{Bit, BitString};
 use crate::bytecode::{Bytecode, Instruction};
 use crate::callable::Callable;
 use crate::coded_function::CodedFunction;
-use crate::pattern::PatternParseMulti;
+use crate::pattern::{Pattern, PatternParse, PatternParseMulti};
+use crate::profile::{NodeId, Profile, ROOT};
+use crate::rope::{BitRope, End};
+use crate::thunk::{Delayed, Thunk};
 use crate::value::Value;
 use itertools::Itertools;
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -24,14 +28,122 @@ pub enum ExecError {
     NotCallable,
     #[error("Value is not a bit string")]
     NotBitString,
+    #[error("Value {value:?} does not match the pattern of a `let` binding: {pattern:?}")]
+    LetNoMatch { pattern: Pattern, value: Value },
+    #[error("Scope stack is empty")]
+    ScopeStackEmpty,
+    #[error("Execution did not finish within {limit} steps")]
+    StepLimitExceeded { limit: usize },
+    #[error("Delayed argument is needed again before its evaluation finished")]
+    ThunkReentered,
+    #[error("Evaluation of the delayed argument failed: {message}")]
+    ThunkFailed { message: String },
 }
 
 pub type BasicExecResult<T> = Result<T, ExecError>;
 pub type ExecResult = BasicExecResult<()>;
 
+/// What a single `VM::step` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepEvent {
+    /// Executed an instruction of the current task, possibly calling a
+    /// native function.
+    Instruction,
+    /// Started a task running a variant of a coded function or its guard,
+    /// either on top of the current one or, for tail calls, instead of it,
+    /// or a task evaluating a delayed argument whose bits are needed.
+    Enter,
+    /// Evaluated the guard of a variant of the function being called, or an
+    /// argument its patterns need, and started the next task of the search
+    /// for the variant.
+    Select,
+    /// Finished the current task and passed its value to the caller.
+    Return,
+}
+
+/// Read-only view of a task on the stack.
+pub struct Frame<'a> {
+    task: &'a Task,
+}
+
+impl<'a> Frame<'a> {
+    pub fn function(&self) -> &'a str {
+        &self.task.origin.function
+    }
+
+    /// Index of the variant being run.
+    pub fn variant(&self) -> usize {
+        self.task.origin.variant
+    }
+
+    /// Source line of the variant, 0 for generated code.
+    pub fn line(&self) -> usize {
+        self.task.origin.line
+    }
+
+    /// Whether the task evaluates the guard of the variant rather than its body.
+    pub fn is_guard(&self) -> bool {
+        self.task.selection.is_some() && self.task.thunk.is_none()
+    }
+
+    /// Whether the task evaluates a delayed argument.
+    pub fn is_argument(&self) -> bool {
+        self.task.thunk.is_some()
+    }
+
+    pub fn local_bindings(&self) -> &'a Bindings {
+        &self.task.local_bindings
+    }
+
+    pub fn value_stack(&self) -> &'a [Value] {
+        &self.task.execution_state.value_stack
+    }
+
+    pub fn prepend(&self) -> BitString {
+        self.task.prepend.to_bit_string()
+    }
+
+    pub fn append(&self) -> BitString {
+        self.task.append.to_bit_string()
+    }
+
+    /// Index of the next instruction to execute.
+    pub fn cursor(&self) -> usize {
+        self.task.execution_state.cursor
+    }
+
+    /// `None` once the task has run all of its instructions.
+    pub fn next_instruction(&self) -> Option<&'a Instruction> {
+        self.task.current_instruction()
+    }
+}
+
 pub struct VM {
     global_bindings: Bindings,
     task_stack: Vec<Task>,
+    /// Value returned by the outermost task, once it finishes.
+    result: Option<Value>,
+    trace: bool,
+    profile: Option<Profile>,
+    /// Values of memoized calls, by function name and arguments.
+    memo: HashMap<MemoKey, Value>,
+    memoize_all: bool,
+    /// Calls of impure natives so far.
+    impure_calls: u64,
+}
+
+type MemoKey = (String, Vec<Value>);
+
+/// A memoized call waiting for its value, which is the string returned at
+/// the end of its chain of tail calls without the first `prepend` and the
+/// last `append` bits, added by the callers.
+#[derive(Debug)]
+struct PendingMemo {
+    key: MemoKey,
+    prepend: usize,
+    append: usize,
+    /// `VM::impure_calls` when the call started.
+    impure_calls: u64,
 }
 
 impl VM {
@@ -39,20 +151,92 @@ impl VM {
         VM {
             global_bindings,
             task_stack: Vec::new(),
+            result: None,
+            trace: false,
+            profile: None,
+            memo: HashMap::new(),
+            memoize_all: false,
+            impure_calls: 0,
         }
     }
 
+    /// Enables or disables printing every executed instruction and returned
+    /// value, which is off by default.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Starts collecting execution statistics, discarding any collected so far.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Memoizes calls of every coded function, not only of those named by
+    /// `pragma memo`.
+    pub fn set_memoize_all(&mut self, memoize_all: bool) {
+        self.memoize_all = memoize_all;
+    }
+
+    /// Number of calls whose value is memoized.
+    pub fn memo_len(&self) -> usize {
+        self.memo.len()
+    }
+
+    pub fn global_bindings(&self) -> &Bindings {
+        &self.global_bindings
+    }
+
+    /// Tasks on the stack, outermost first.
+    pub fn frames(&self) -> Vec<Frame<'_>> {
+        self.task_stack.iter().map(|task| Frame { task }).collect()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.task_stack.len()
+    }
+
+    /// Value returned by the outermost task, once the task stack is empty.
+    pub fn result(&self) -> Option<&Value> {
+        self.result.as_ref()
+    }
+
+    /// Steps until the task stack is empty and returns the result of the
+    /// outermost task, giving up after `step_limit` steps if it is set.
+    pub fn run(&mut self, step_limit: Option<usize>) -> BasicExecResult<Value> {
+        let mut steps = 0;
+        while !self.task_stack.is_empty() {
+            if let Some(limit) = step_limit {
+                if steps == limit {
+                    let error = ExecError::StepLimitExceeded { limit };
+                    self.fail_thunks(&error);
+                    return Err(error);
+                }
+            }
+            self.step()?;
+            steps += 1;
+        }
+
+        self.result.take().ok_or(ExecError::ValueStackEmpty)
+    }
+
     pub fn invoke_by_name(&mut self, name: &str, arguments: Vec<Value>) -> ExecResult {
         let callable = self
             .global_bindings
             .get_value(name)
-            .map(Clone::clone)
+            .cloned()
             .ok_or_else(|| ExecError::VariableNotFound {
                 name: String::from(name),
             })?
             .into_callable()
-            .ok_or_else(|| ExecError::NotCallable)?;
+            .ok_or(ExecError::NotCallable)?;
 
+        if let Some(profile) = &mut self.profile {
+            profile.record_call(callable.name(), false);
+        }
         self.invoke(callable, arguments, BitString::empty(), BitString::empty())
     }
 
@@ -63,84 +247,261 @@ impl VM {
         prepend: BitString,
         append: BitString,
     ) -> ExecResult {
+        self.invoke_with_ropes(
+            callable,
+            arguments,
+            BitRope::from_bit_string(prepend, End::Back),
+            BitRope::from_bit_string(append, End::Front),
+            Vec::new(),
+        )?;
+        Ok(())
+    }
+
+    /// Starts a task for `callable` or, for natives and memoized values,
+    /// returns at once. Returns whether a task was started. `memos` are the
+    /// calls that end with this one.
+    fn invoke_with_ropes(
+        &mut self,
+        callable: Callable,
+        arguments: Vec<Value>,
+        prepend: BitRope,
+        append: BitRope,
+        mut memos: Vec<PendingMemo>,
+    ) -> BasicExecResult<bool> {
         match callable {
             Callable::Coded(coded_function) => {
-                self.task_stack
-                    .push(make_task(coded_function, arguments, prepend, append)?);
+                if self.memoize_all || coded_function.memoized {
+                    let values = arguments.iter().cloned().map(Value::resolve).collect();
+                    let key = (coded_function.name.clone(), values);
+                    if let Some(value) = self.memo.get(&key).cloned() {
+                        self.return_value(value, prepend, append, memos, &coded_function.name)?;
+                        return Ok(false);
+                    }
+                    memos.push(PendingMemo {
+                        key,
+                        prepend: prepend.len(),
+                        append: append.len(),
+                        impure_calls: self.impure_calls,
+                    });
+                }
+                let task = make_task(
+                    coded_function,
+                    arguments,
+                    prepend,
+                    append,
+                    memos,
+                    self.trace,
+                    self.profile.as_mut(),
+                )?;
+                self.enter(task);
+                Ok(true)
             }
             Callable::Native(native_function) => {
+                if !native_function.pure {
+                    self.impure_calls += 1;
+                }
+                let arguments = arguments.into_iter().map(Value::resolve).collect();
                 let ret = (native_function.func)(arguments)?;
-                self.task_stack
-                    .last_mut()
-                    .ok_or(ExecError::TaskStackEmpty)?
-                    .execution_state
-                    .value_stack
-                    .push(ret)
+                self.return_value(ret, prepend, append, memos, &native_function.name)?;
+                Ok(false)
             }
         }
+    }
 
-        Ok(())
+    pub fn step(&mut self) -> BasicExecResult<StepEvent> {
+        let result = self.try_step();
+        if let Err(error) = &result {
+            self.fail_thunks(error);
+        }
+        result
     }
 
-    pub fn step(&mut self) -> ExecResult {
+    /// Marks the delayed arguments being evaluated as failed with `error`,
+    /// since their tasks do not finish.
+    fn fail_thunks(&self, error: &ExecError) {
+        for task in &self.task_stack {
+            if let Some(thunk) = &task.thunk {
+                thunk.fail(error);
+            }
+        }
+    }
+
+    fn try_step(&mut self) -> BasicExecResult<StepEvent> {
         let current_task = self
             .task_stack
             .last_mut()
             .ok_or(ExecError::TaskStackEmpty)?;
-        let step_result = current_task.step(&self.global_bindings)?;
+        // The instruction is run once the argument is evaluated.
+        if let Some(thunk) = current_task.unevaluated_operand(self.memoize_all) {
+            let thunk = thunk.clone();
+            self.enter(force(thunk, None)?);
+            return Ok(StepEvent::Enter);
+        }
+        let is_cat = matches!(
+            current_task.current_instruction(),
+            Some(Instruction::Cat(_))
+        );
+        let step_result = current_task.step(&self.global_bindings, self.trace)?;
+
+        if let Some(profile) = &mut self.profile {
+            if !matches!(step_result, StepResult::FinishTask { .. }) {
+                profile.record_instruction(current_task.profile_node);
+            }
+            if is_cat {
+                if let Some(Value::BitString(s)) = current_task.execution_state.value_stack.last() {
+                    profile.record_bits(&current_task.origin.function, s.len());
+                }
+            }
+            if let StepResult::Call { callable, tail, .. } = &step_result {
+                let is_tail = matches!(tail, TailStatus::Tail { .. });
+                profile.record_call(callable.name(), is_tail);
+            }
+        }
 
         match step_result {
-            StepResult::Nothing => (),
+            StepResult::Nothing => Ok(StepEvent::Instruction),
             StepResult::Call {
                 callable,
                 arguments,
                 tail,
-            } => match tail {
-                TailStatus::NotTail => {
-                    self.invoke(callable, arguments, BitString::empty(), BitString::empty())?
-                }
-                TailStatus::Tail { prepends, appends } => {
-                    let current_task = self.task_stack.pop().unwrap();
-                    let prepend = current_task
-                        .prepend
-                        .into_iter()
-                        .chain(prepends.into_iter().map(|x| x.into_iter()).flatten())
-                        .collect();
-                    let append = appends
-                        .into_iter()
-                        .map(|x| x.into_iter())
-                        .flatten()
-                        .chain(current_task.append.into_iter())
-                        .collect();
-                    self.invoke(callable, arguments, prepend, append)?;
-                }
-            },
+            } => {
+                let is_tail = matches!(tail, TailStatus::Tail { .. });
+                Ok(match (self.call(callable, arguments, tail)?, is_tail) {
+                    (true, _) => StepEvent::Enter,
+                    (false, false) => StepEvent::Instruction,
+                    (false, true) => StepEvent::Return,
+                })
+            }
             StepResult::FinishTask { return_value } => {
                 let current_task = self.task_stack.pop().unwrap();
-                let pushed_value = match return_value {
-                    Value::BitString(s) => current_task
-                        .prepend
-                        .into_iter()
-                        .chain(s.into_iter())
-                        .chain(current_task.append.into_iter())
-                        .collect::<BitString>()
-                        .into(),
-                    Value::Callable(c) => {
-                        if !current_task.prepend.is_empty() || !current_task.append.is_empty() {
-                            return Err(ExecError::NotBitString);
+                let return_value = return_value.resolve();
+                if let Some(thunk) = &current_task.thunk {
+                    thunk.finish(return_value);
+                    return match current_task.selection {
+                        Some(selection) => {
+                            let task = selection.next_task(self.trace, self.profile.as_mut())?;
+                            self.enter(task);
+                            Ok(StepEvent::Select)
                         }
-                        c.into()
-                    }
-                };
+                        None => Ok(StepEvent::Return),
+                    };
+                }
+                if let Some(selection) = current_task.selection {
+                    let mut task = selection.resume(
+                        return_value,
+                        current_task.local_bindings,
+                        self.trace,
+                        self.profile.as_mut(),
+                    )?;
+                    task.profile_node = current_task.profile_node;
+                    self.task_stack.push(task);
+                    return Ok(StepEvent::Select);
+                }
+
+                self.return_value(
+                    return_value,
+                    current_task.prepend,
+                    current_task.append,
+                    current_task.memos,
+                    &current_task.origin.function,
+                )?;
+                Ok(StepEvent::Return)
+            }
+        }
+    }
 
-                println!("Return: {:?}", pushed_value);
-                self.task_stack
-                    .last_mut()
-                    .ok_or(ExecError::TaskStackEmpty)?
-                    .push(pushed_value);
+    /// Pushes a task started by a call or by the evaluation of an argument.
+    fn enter(&mut self, mut task: Task) {
+        if let Some(profile) = &mut self.profile {
+            let parent = self.task_stack.last().map_or(ROOT, |x| x.profile_node);
+            task.profile_node = profile.node(parent, &task.origin.function);
+        }
+        self.task_stack.push(task);
+    }
+
+    /// Returns whether a task was started.
+    fn call(
+        &mut self,
+        callable: Callable,
+        arguments: Vec<Value>,
+        tail: TailStatus,
+    ) -> BasicExecResult<bool> {
+        match tail {
+            TailStatus::NotTail => self.invoke_with_ropes(
+                callable,
+                arguments,
+                BitRope::new(End::Back),
+                BitRope::new(End::Front),
+                Vec::new(),
+            ),
+            TailStatus::Tail { prepends, appends } => {
+                let mut current_task = self.task_stack.pop().unwrap();
+                // Only the new pieces are added: the bits are copied once, on return.
+                let mut prepend = current_task.prepend.clone();
+                for piece in prepends {
+                    prepend.push(piece);
+                }
+                let mut append = current_task.append.clone();
+                for piece in appends.into_iter().rev() {
+                    append.push(piece);
+                }
+                // The callee finishes the memoized calls of the caller.
+                let memos = std::mem::take(&mut current_task.memos);
+                // Keep the caller around if the call fails, so that it can be inspected.
+                self.invoke_with_ropes(callable, arguments, prepend, append, memos)
+                    .inspect_err(|_| self.task_stack.push(current_task))
             }
         }
+    }
 
+    /// Passes the value returned by a call to the task waiting for it and
+    /// records it for the memoized calls that end with it, unless an impure
+    /// native was called since they started. `function` is the name of the
+    /// returning function.
+    fn return_value(
+        &mut self,
+        value: Value,
+        prepend: BitRope,
+        append: BitRope,
+        memos: Vec<PendingMemo>,
+        function: &str,
+    ) -> ExecResult {
+        let pushed_value = match value {
+            Value::BitString(s) if prepend.is_empty() && append.is_empty() => s.into(),
+            Value::BitString(s) => {
+                let pieces = prepend.pieces().into_iter().chain([&s]);
+                let s = BitString::concat_all(pieces.chain(append.pieces()));
+                if let Some(profile) = &mut self.profile {
+                    profile.record_bits(function, s.len());
+                }
+                s.into()
+            }
+            value => {
+                if !prepend.is_empty() || !append.is_empty() {
+                    return Err(ExecError::NotBitString);
+                }
+                value
+            }
+        };
+
+        for memo in memos {
+            if memo.impure_calls != self.impure_calls {
+                continue;
+            }
+            let value = match &pushed_value {
+                Value::BitString(s) => s.slice(memo.prepend, s.len() - memo.append).into(),
+                value => value.clone(),
+            };
+            self.memo.insert(memo.key, value);
+        }
+
+        if self.trace {
+            println!("Return: {:?}", pushed_value);
+        }
+        match self.task_stack.last_mut() {
+            Some(task) => task.push(pushed_value),
+            None => self.result = Some(pushed_value),
+        }
         Ok(())
     }
 }
@@ -182,17 +543,77 @@ enum StepResult {
     },
 }
 
+/// Names bound by a single `let`, along with the values they shadowed.
+type Scope = Vec<(String, Option<Value>)>;
+
 #[derive(Debug)]
 struct Task {
+    origin: Origin,
     bytecode: Bytecode,
     local_bindings: Bindings,
+    scopes: Vec<Scope>,
     execution_state: ExecutionState,
-    prepend: BitString,
-    append: BitString,
+    prepend: BitRope,
+    append: BitRope,
+    /// Memoized calls whose value is the one this task returns.
+    memos: Vec<PendingMemo>,
+    /// Set if this task evaluates a guard or an argument needed to match the
+    /// patterns of a variant, rather than a function body.
+    selection: Option<Selection>,
+    /// Set if this task evaluates a delayed argument.
+    thunk: Option<Thunk>,
+    /// Call path of the task, if the VM is profiling.
+    profile_node: NodeId,
+}
+
+/// Variant of a coded function that a task runs.
+#[derive(Debug)]
+struct Origin {
+    function: String,
+    variant: usize,
+    line: usize,
 }
 
 impl Task {
-    fn step(&mut self, global_bindings: &Bindings) -> BasicExecResult<StepResult> {
+    fn new(
+        origin: Origin,
+        bytecode: Bytecode,
+        local_bindings: Bindings,
+        prepend: BitRope,
+        append: BitRope,
+        memos: Vec<PendingMemo>,
+    ) -> Task {
+        Task {
+            origin,
+            bytecode,
+            local_bindings,
+            scopes: Vec::new(),
+            execution_state: ExecutionState::new(),
+            prepend,
+            append,
+            memos,
+            selection: None,
+            thunk: None,
+            profile_node: ROOT,
+        }
+    }
+
+    fn guard(bytecode: Bytecode, local_bindings: Bindings, selection: Selection) -> Task {
+        let origin = selection.origin();
+        Task {
+            selection: Some(selection),
+            ..Task::new(
+                origin,
+                bytecode,
+                local_bindings,
+                BitRope::new(End::Back),
+                BitRope::new(End::Front),
+                Vec::new(),
+            )
+        }
+    }
+
+    fn step(&mut self, global_bindings: &Bindings, trace: bool) -> BasicExecResult<StepResult> {
         let instruction = match self.current_instruction() {
             Some(x) => x,
             None => {
@@ -203,9 +624,24 @@ impl Task {
         }
         .clone();
 
-        println!("{:?}", instruction);
+        if trace {
+            println!("{:?}", instruction);
+        }
 
         self.execution_state.cursor += 1;
+        let result = self.execute(instruction, global_bindings);
+        if result.is_err() {
+            // Stay at the failing instruction, where the debugger shows it.
+            self.execution_state.cursor -= 1;
+        }
+        result
+    }
+
+    fn execute(
+        &mut self,
+        instruction: Instruction,
+        global_bindings: &Bindings,
+    ) -> BasicExecResult<StepResult> {
         Ok(match instruction {
             Instruction::LoadConst(bit_string) => {
                 self.push(bit_string.clone().into());
@@ -220,7 +656,7 @@ impl Task {
                 StepResult::Nothing
             }
             Instruction::Trampoline => {
-                let value = self.pop_result()?;
+                let value = self.pop_result()?.resolve();
                 match value {
                     Value::Callable(Callable::Coded(f)) if f.is_trampoline_callable() => {
                         self.execution_state.cursor -= 1;
@@ -255,11 +691,10 @@ impl Task {
                     .map(|x| x.into_bit_string().ok_or(ExecError::NotBitString))
                     .try_collect()?;
 
-                let bit_string: BitString = children
-                    .into_iter()
-                    .map(|x| x.into_iter())
-                    .flatten()
-                    .collect();
+                let mut bit_string = BitString::empty();
+                for child in &children {
+                    bit_string.append(child);
+                }
 
                 self.execution_state.value_stack.push(bit_string.into());
                 StepResult::Nothing
@@ -272,8 +707,6 @@ impl Task {
                     .checked_sub(prepend + append + 1)
                     .ok_or(ExecError::ValueStackEmpty)?;
 
-                dbg!(num_args);
-
                 let arguments = self.pop_n_result(num_args)?;
 
                 let callable = self
@@ -281,8 +714,6 @@ impl Task {
                     .into_callable()
                     .ok_or(ExecError::NotCallable)?;
 
-                dbg!("here");
-
                 let appends = self
                     .pop_n_result(append)?
                     .into_iter()
@@ -308,9 +739,121 @@ impl Task {
                     tail: TailStatus::Tail { prepends, appends },
                 }
             }
+            Instruction::Bind(pattern) => {
+                let value = self.pop_result()?;
+                let bindings = pattern
+                    .parse(value.clone())
+                    .ok_or(ExecError::LetNoMatch { pattern, value })?;
+                self.bind(bindings);
+                StepResult::Nothing
+            }
+            Instruction::Unbind => {
+                self.unbind()?;
+                StepResult::Nothing
+            }
+            Instruction::Delay(bytecode) => {
+                let value = self.delay(bytecode, global_bindings)?;
+                self.push(value);
+                StepResult::Nothing
+            }
         })
     }
 
+    /// A thunk evaluating `bytecode` in the current scope. A variable is
+    /// passed on as it is, without a thunk, unless it names a function of
+    /// no arguments: that one is only called when the argument is needed.
+    fn delay(&self, bytecode: Bytecode, global_bindings: &Bindings) -> BasicExecResult<Value> {
+        if let Some((Instruction::LoadVar { name }, Instruction::Trampoline)) =
+            bytecode.iter().collect_tuple()
+        {
+            let value = self
+                .get_var(name, global_bindings)
+                .ok_or_else(|| ExecError::VariableNotFound { name: name.clone() })?;
+            if !matches!(&value, Value::Callable(Callable::Coded(f)) if f.is_trampoline_callable())
+            {
+                return Ok(value);
+            }
+        }
+
+        Ok(Value::Thunk(Thunk::new(Delayed {
+            function: self.origin.function.clone(),
+            variant: self.origin.variant,
+            line: self.origin.line,
+            bytecode,
+            bindings: self.local_bindings.clone(),
+        })))
+    }
+
+    /// A thunk on the value stack that has to be evaluated before the next
+    /// instruction runs: an operand of `cat`, a piece prepended or appended by
+    /// a tail call, the function called, the arguments of natives and of
+    /// memoized functions, a value matched against a pattern, a variable
+    /// that may name a function to trampoline into, or the returned value.
+    /// Arguments of other calls are passed on as they are.
+    fn unevaluated_operand(&self, memoize_all: bool) -> Option<&Thunk> {
+        let stack = &self.execution_state.value_stack;
+        let top = |n: usize| &stack[stack.len().saturating_sub(n)..];
+        // The function called, then its arguments if they are needed now.
+        let call = |index: usize| {
+            let callee = stack.get(index)?;
+            if let Some(thunk) = callee.unevaluated() {
+                return Some(thunk);
+            }
+            let strict = match callee.clone().resolve() {
+                Value::Callable(Callable::Native(_)) => true,
+                Value::Callable(Callable::Coded(f)) => memoize_all || f.memoized,
+                _ => false,
+            };
+            if strict {
+                stack[index + 1..].iter().find_map(Value::unevaluated)
+            } else {
+                None
+            }
+        };
+
+        match self.current_instruction() {
+            None | Some(Instruction::Trampoline) => top(1).iter().find_map(Value::unevaluated),
+            Some(Instruction::Cat(n)) => top(*n).iter().find_map(Value::unevaluated),
+            Some(Instruction::Bind(pattern)) if pattern.needs_bits() => {
+                top(1).iter().find_map(Value::unevaluated)
+            }
+            Some(Instruction::Call(n)) => call(stack.len().checked_sub(n + 1)?),
+            Some(Instruction::Tail { prepend, append }) => {
+                let pieces = stack.get(..prepend + append)?;
+                pieces
+                    .iter()
+                    .find_map(Value::unevaluated)
+                    .or_else(|| call(prepend + append))
+            }
+            _ => None,
+        }
+    }
+
+    fn bind(&mut self, bindings: Bindings) {
+        let scope = bindings
+            .into_map()
+            .into_iter()
+            .map(|(name, value)| {
+                let shadowed = self.local_bindings.replace(name.clone(), value);
+                (name, shadowed)
+            })
+            .collect();
+        self.scopes.push(scope);
+    }
+
+    fn unbind(&mut self) -> ExecResult {
+        let scope = self.scopes.pop().ok_or(ExecError::ScopeStackEmpty)?;
+        for (name, shadowed) in scope {
+            match shadowed {
+                Some(value) => self.local_bindings.add(name, value),
+                None => {
+                    self.local_bindings.remove(&name);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn current_instruction(&self) -> Option<&Instruction> {
         self.bytecode.at(self.execution_state.cursor)
     }
@@ -331,7 +874,7 @@ impl Task {
         self.local_bindings
             .get_value(name)
             .or_else(|| global_bindings.get_value(name))
-            .map(|x| x.clone())
+            .cloned()
     }
 
     fn push(&mut self, value: Value) {
@@ -361,30 +904,162 @@ impl Task {
     }
 }
 
+/// An unfinished search for the variant of a function to invoke.
+#[derive(Debug)]
+struct Selection {
+    function: CodedFunction,
+    arguments: Vec<Value>,
+    next_variant: usize,
+    prepend: BitRope,
+    append: BitRope,
+    memos: Vec<PendingMemo>,
+}
+
+impl Selection {
+    /// Returns either the task running the body of the first matching variant
+    /// or, if that variant is guarded, the task evaluating its guard.
+    fn next_task(
+        mut self,
+        trace: bool,
+        mut profile: Option<&mut Profile>,
+    ) -> BasicExecResult<Task> {
+        while let Some(var) = self.function.variants.get(self.next_variant) {
+            // Matching goes on once the argument is evaluated.
+            let unevaluated = self
+                .arguments
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| var.patterns.needs_bits(*i))
+                .find_map(|(_, arg)| arg.unevaluated());
+            if let Some(thunk) = unevaluated {
+                let thunk = thunk.clone();
+                return force(thunk, Some(self));
+            }
+            if trace {
+                println!("Parse {:?} with {:?}", self.arguments, var.patterns);
+            }
+            let parsed = var.patterns.parse(self.arguments.clone());
+            if let Some(profile) = profile.as_deref_mut() {
+                profile.record_match(
+                    &self.function.name,
+                    self.next_variant,
+                    var.line,
+                    parsed.is_some(),
+                );
+            }
+            let local_bindings = match parsed {
+                Some(x) => x,
+                None => {
+                    self.next_variant += 1;
+                    continue;
+                }
+            };
+
+            return Ok(match var.guard.clone() {
+                Some(guard) => Task::guard(guard, local_bindings, self),
+                None => {
+                    if let Some(profile) = profile {
+                        profile.record_selected(&self.function.name, self.next_variant);
+                    }
+                    let bytecode = var.body.clone();
+                    Task::new(
+                        self.origin(),
+                        bytecode,
+                        local_bindings,
+                        self.prepend,
+                        self.append,
+                        self.memos,
+                    )
+                }
+            });
+        }
+
+        Err(ExecError::NoMatch {
+            func_name: self.function.name,
+            args: self.arguments,
+        })
+    }
+
+    fn origin(&self) -> Origin {
+        Origin {
+            function: self.function.name.clone(),
+            variant: self.next_variant,
+            line: self.function.variants[self.next_variant].line,
+        }
+    }
+
+    /// Continues the search once the guard of the current variant is evaluated.
+    /// Only the one-bit string `1` selects the variant.
+    fn resume(
+        mut self,
+        guard_value: Value,
+        local_bindings: Bindings,
+        trace: bool,
+        profile: Option<&mut Profile>,
+    ) -> BasicExecResult<Task> {
+        let selected = match guard_value {
+            Value::BitString(s) => s.len() == 1 && s.bit_at(0) == Some(Bit::One),
+            _ => false,
+        };
+
+        if selected {
+            if let Some(profile) = profile {
+                profile.record_selected(&self.function.name, self.next_variant);
+            }
+            let bytecode = self.function.variants[self.next_variant].body.clone();
+            Ok(Task::new(
+                self.origin(),
+                bytecode,
+                local_bindings,
+                self.prepend,
+                self.append,
+                self.memos,
+            ))
+        } else {
+            self.next_variant += 1;
+            self.next_task(trace, profile)
+        }
+    }
+}
+
 fn make_task(
     coded_function: CodedFunction,
     arguments: Vec<Value>,
-    prepend: BitString,
-    append: BitString,
+    prepend: BitRope,
+    append: BitRope,
+    memos: Vec<PendingMemo>,
+    trace: bool,
+    profile: Option<&mut Profile>,
 ) -> BasicExecResult<Task> {
-    for var in coded_function.variants {
-        let local_bindings = match var.patterns.parse(arguments.clone()) {
-            Some(x) => x,
-            None => continue,
-        };
-        let bytecode = var.body;
-
-        return Ok(Task {
-            bytecode,
-            local_bindings,
-            execution_state: ExecutionState::new(),
-            prepend,
-            append,
-        });
+    Selection {
+        function: coded_function,
+        arguments,
+        next_variant: 0,
+        prepend,
+        append,
+        memos,
     }
+    .next_task(trace, profile)
+}
 
-    Err(ExecError::NoMatch {
-        func_name: coded_function.name,
-        args: arguments,
+/// Task evaluating a delayed argument, continuing `selection` once it is done.
+fn force(thunk: Thunk, selection: Option<Selection>) -> BasicExecResult<Task> {
+    let delayed = thunk.start()?;
+    let origin = Origin {
+        function: delayed.function,
+        variant: delayed.variant,
+        line: delayed.line,
+    };
+    Ok(Task {
+        selection,
+        thunk: Some(thunk),
+        ..Task::new(
+            origin,
+            delayed.bytecode,
+            delayed.bindings,
+            BitRope::new(End::Back),
+            BitRope::new(End::Front),
+            Vec::new(),
+        )
     })
 }
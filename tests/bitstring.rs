@@ -0,0 +1,327 @@
+use bitmachine::bitstring::{Bit, BitString};
+use bitmachine::prelude;
+use bitmachine::value::Value;
+use std::collections::{BTreeSet, HashSet};
+
+/// Bits of a fixed pseudo-random pattern, so that no two bytes are alike.
+fn bits(len: usize, seed: usize) -> Vec<Bit> {
+    (0..len)
+        .map(
+            |i| match (i * 7 + seed) % 5 < 2 || (i + seed).is_multiple_of(11) {
+                true => Bit::One,
+                false => Bit::Zero,
+            },
+        )
+        .collect()
+}
+
+fn to_vec(s: &BitString) -> Vec<Bit> {
+    s.iter().collect()
+}
+
+const LENGTHS: [usize; 9] = [0, 1, 7, 8, 9, 63, 64, 65, 200];
+
+#[test]
+fn slices_share_bits() {
+    let model = bits(200, 3);
+    let s: BitString = model.iter().copied().collect();
+    for start in [0, 1, 5, 8, 13, 64, 100] {
+        for end in [start, start + 1, start + 9, start + 64, start + 70, 200] {
+            let end = end.min(200);
+            let slice = s.slice(start, end);
+            assert_eq!(to_vec(&slice), model[start..end]);
+            assert_eq!(slice.len(), end - start);
+            assert_eq!(slice.bit_at(end - start), None);
+        }
+    }
+}
+
+#[test]
+fn concat_at_every_alignment() {
+    for &a_len in &LENGTHS {
+        for &b_len in &LENGTHS {
+            for offset in [0, 1, 3, 8] {
+                let a_model = bits(a_len + offset, 1);
+                let b_model = bits(b_len + offset, 2);
+                let a = a_model
+                    .iter()
+                    .copied()
+                    .collect::<BitString>()
+                    .slice(offset, a_len + offset);
+                let b = b_model
+                    .iter()
+                    .copied()
+                    .collect::<BitString>()
+                    .slice(offset, b_len + offset);
+
+                let expected: Vec<Bit> = a_model[offset..]
+                    .iter()
+                    .chain(&b_model[offset..])
+                    .copied()
+                    .collect();
+                assert_eq!(to_vec(&a.concat(&b)), expected);
+                assert_eq!(
+                    to_vec(&BitString::concat_all([&b, &a, &b])).len(),
+                    2 * b_len + a_len
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn equality_ignores_storage() {
+    let model = bits(150, 4);
+    let whole: BitString = model.iter().copied().collect();
+    for start in [0, 1, 9, 70] {
+        let copy: BitString = model[start..].iter().copied().collect();
+        assert_eq!(whole.slice(start, 150), copy);
+        assert_ne!(whole.slice(start, 149), copy);
+    }
+    assert_ne!(whole.slice(0, 64), whole.slice(1, 65));
+    assert_eq!(whole.slice(3, 3), BitString::empty());
+}
+
+#[test]
+fn bytes_are_padded() {
+    let s: BitString = "1011001110".parse().unwrap();
+    assert_eq!(s.to_bytes(), vec![0b1011_0011, 0b1000_0000]);
+    assert_eq!(s.slice(1, 10).to_bytes(), vec![0b0110_0111, 0]);
+    assert_eq!(BitString::from_u64(5).slice(61, 64), "101".parse().unwrap());
+}
+
+#[test]
+fn append_and_prepend_match_concat() {
+    for &a_len in &LENGTHS {
+        for &b_len in &LENGTHS {
+            let a: BitString = bits(a_len + 3, 5).into_iter().collect();
+            let b: BitString = bits(b_len, 6).into_iter().collect();
+            let a = a.slice(3, a_len + 3);
+
+            let mut appended = a.clone();
+            appended.append(&b);
+            assert_eq!(appended, a.concat(&b));
+            let mut prepended = a.clone();
+            prepended.prepend(&b);
+            assert_eq!(prepended, b.concat(&a));
+        }
+    }
+}
+
+#[test]
+fn append_in_place_clears_leftover_bits() {
+    let whole: BitString = "1111111111111".parse().unwrap();
+    let mut start = whole.slice(0, 10);
+    drop(whole);
+    start.append(&"0".parse().unwrap());
+    start.push_u64(0, 2);
+    assert_eq!(start, "1111111111000".parse().unwrap());
+}
+
+#[test]
+fn words_in_and_out() {
+    let mut s = BitString::empty();
+    s.push_u64(0b101, 3);
+    s.push_u64(u64::MAX, 64);
+    s.push_u64(0, 0);
+    s.push_u64(0b10, 2);
+    assert_eq!(s.len(), 69);
+    assert_eq!(s.get_u64(0, 3), 0b101);
+    assert_eq!(s.get_u64(3, 64), u64::MAX);
+    assert_eq!(s.get_u64(66, 3), 0b110);
+    assert_eq!(s.get_u64(5, 0), 0);
+    assert_eq!(s.get_u64(5, 64), u64::MAX << 2 | 0b10);
+    assert_eq!(BitString::from_u64(12345).get_u64(0, 64), 12345);
+}
+
+#[test]
+fn ranges_compare_across_alignments() {
+    let model = bits(300, 7);
+    let s: BitString = model.iter().copied().collect();
+    let shifted: BitString = model[5..].iter().copied().collect();
+    assert!(s.range_eq(5, &shifted, 0, 295));
+    assert!(s.range_eq(100, &shifted, 95, 130));
+    assert!(!s.range_eq(4, &shifted, 0, 100));
+    assert!(s.range_eq(0, &shifted, 0, 0));
+}
+
+fn b(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+#[test]
+fn strings_order_lexicographically_then_by_length() {
+    let sorted = [
+        ".",
+        "0",
+        "00",
+        "0000001",
+        "01",
+        "1",
+        "10",
+        "1000000000000000000000000000000000000000000000000000000000000000000001",
+        "11",
+    ];
+    let mut strings: Vec<BitString> = sorted.iter().rev().map(|x| b(x)).collect();
+    strings.sort();
+    let texts: Vec<String> = strings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(texts, sorted);
+    assert!(b("0110") < b("0111"));
+    assert_eq!(BitString::default(), BitString::empty());
+}
+
+#[test]
+fn equal_strings_hash_alike() {
+    let model = bits(200, 1);
+    let s: BitString = model.iter().copied().collect();
+    let mut set = HashSet::new();
+    set.insert(s.slice(3, 150));
+    set.insert(s.slice(3, 150).concat(&BitString::empty()));
+    let copy: BitString = model[3..150].iter().copied().collect();
+    assert!(set.contains(&copy));
+    set.insert(s.slice(3, 149));
+    set.insert(s.slice(4, 150));
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn iterates_both_ways() {
+    let model = bits(150, 4);
+    let s: BitString = model.iter().copied().collect();
+    let slice = s.slice(3, 140);
+    assert_eq!((&slice).into_iter().len(), 137);
+    assert_eq!(slice.iter().collect::<Vec<_>>(), model[3..140]);
+    let mut reversed = model[3..140].to_vec();
+    reversed.reverse();
+    assert_eq!(
+        slice.clone().into_iter().rev().collect::<Vec<_>>(),
+        reversed
+    );
+
+    let mut iter = slice.into_iter();
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+    while let (Some(x), Some(y)) = (iter.next(), iter.next_back()) {
+        front.push(x);
+        back.push(y);
+    }
+    back.reverse();
+    front.extend(back);
+    assert_eq!(front.len(), 136);
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn extends_and_indexes() {
+    let mut s = b("101");
+    s.extend(bits(70, 2));
+    s.extend(vec![b("11"), BitString::empty(), b("0")]);
+    assert_eq!(s.len(), 76);
+    assert_eq!(s[0], Bit::One);
+    assert_eq!(s[1], Bit::Zero);
+    assert_eq!(s[75], Bit::Zero);
+    assert_eq!(to_vec(&s.slice(3, 73)), bits(70, 2));
+    assert_eq!(s.slice(73, 76), b("110"));
+}
+
+#[test]
+#[should_panic(expected = "out of range")]
+fn indexing_past_the_end_panics() {
+    let _ = b("10")[2];
+}
+
+#[test]
+fn bitwise_operators() {
+    let model_a = bits(130, 1);
+    let model_b = bits(130, 6);
+    let a: BitString = model_a.iter().copied().collect();
+    let c: BitString = model_b.iter().copied().collect();
+    let zip = |f: fn(u8, u8) -> u8| -> BitString {
+        model_a
+            .iter()
+            .zip(&model_b)
+            .map(|(x, y)| Bit::from_number(f(x.as_number(), y.as_number())).unwrap())
+            .collect()
+    };
+    assert_eq!(&a & &c, zip(|x, y| x & y));
+    assert_eq!(&a | &c, zip(|x, y| x | y));
+    assert_eq!(a.clone() ^ c.clone(), zip(|x, y| x ^ y));
+    assert_eq!(!&a, zip(|x, _| 1 - x));
+    assert_eq!(!b("0110"), b("1001"));
+    assert_eq!(!BitString::empty(), BitString::empty());
+    assert_eq!(b("1100") & b("1010"), b("1000"));
+}
+
+#[test]
+#[should_panic(expected = "Cannot and strings of 3 and 2 bits")]
+fn bitwise_operators_need_equal_lengths() {
+    let _ = b("101") & b("10");
+}
+
+#[test]
+fn checked_bitwise_operations_report_unequal_lengths() {
+    assert_eq!(b("1100").checked_and(&b("1010")), Some(b("1000")));
+    assert_eq!(b("1100").checked_or(&b("1010")), Some(b("1110")));
+    assert_eq!(b("1100").checked_xor(&b("1010")), Some(b("0110")));
+    assert_eq!(b("101").checked_and(&b("10")), None);
+    assert_eq!(b("101").checked_or(&BitString::empty()), None);
+    assert_eq!(b("1").checked_xor(&b("10")), None);
+}
+
+#[test]
+fn shifts_keep_the_length() {
+    assert_eq!(b("10110") << 2, b("11000"));
+    assert_eq!(b("10110") >> 2, b("00101"));
+    assert_eq!(b("10110") << 0, b("10110"));
+    assert_eq!(b("10110") >> 9, b("00000"));
+    assert_eq!(&b("10110") << 5, b("00000"));
+    let long: BitString = bits(200, 3).into_iter().collect();
+    assert_eq!((&long << 70).slice(0, 130), long.slice(70, 200));
+    assert_eq!((&long >> 70).slice(70, 200), long.slice(0, 130));
+}
+
+#[test]
+fn formatting() {
+    let s = b("1011001110");
+    assert_eq!(s.to_string(), "1011001110");
+    assert_eq!(format!("{:#}", s), "10110011_10");
+    assert_eq!(format!("{:>12}|", b("01")), "          01|");
+    assert_eq!(BitString::empty().to_string(), ".");
+    assert_eq!(format!("{:b}", s), "1011001110");
+    assert_eq!(format!("{:#b}", s), "0b1011001110");
+    assert_eq!(format!("{:x}", s), "2ce");
+    assert_eq!(format!("{:#X}", s), "0x2CE");
+    assert_eq!(format!("{:x}", b("00000000")), "00");
+    assert_eq!(format!("{:#010x}", b("11111111")), "0x000000ff");
+    assert_eq!(format!("{:x}", BitString::empty()), "");
+}
+
+#[test]
+fn values_compare_hash_and_display() {
+    let bindings = prelude::make_bindings();
+    let not = bindings.get_value("not").unwrap().clone();
+    let and = bindings.get_value("and").unwrap().clone();
+    let values = vec![
+        Value::from(b("101")),
+        Value::from(b(".")),
+        not.clone(),
+        and.clone(),
+    ];
+    assert_eq!(Value::from(b("101")), values[0]);
+    assert_eq!(not, bindings.get_value("not").unwrap().clone());
+    assert_ne!(not, and);
+    assert_ne!(Value::from(b("1")), not);
+
+    let set: HashSet<Value> = values.iter().cloned().chain(values.clone()).collect();
+    assert_eq!(set.len(), 4);
+    let texts: BTreeSet<String> = values.iter().map(Value::to_string).collect();
+    assert_eq!(
+        texts.into_iter().collect::<Vec<_>>(),
+        [
+            ".",
+            "101",
+            "<function prelude.and>",
+            "<function prelude.not>"
+        ]
+    );
+}
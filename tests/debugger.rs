@@ -0,0 +1,111 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const SOURCE: &str = "\
+inc x+0 = x+1
+inc x+1 = (inc x)+0
+inc .   = 1
+
+pick x | eq x 11 = 1
+pick x           = 0
+
+main = 0+(pick (inc (inc 1)))
+";
+
+/// Runs `bitmachine debug` on `source` with `commands` and returns what it printed.
+fn session(name: &str, source: &str, commands: &str) -> String {
+    let path = std::env::temp_dir().join(format!("bitmachine-debugger-{}.bm", name));
+    std::fs::write(&path, source).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+        .arg("debug")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn function_breakpoint_shows_the_stack() {
+    let output = session("function", SOURCE, "break inc\ncontinue\nstack\n");
+    assert!(output.contains("Breakpoint: inc variant 1 (line 2)"));
+    assert!(output.contains("#0 inc variant 1 (line 2)"));
+    assert!(output.contains("    x = .\n"));
+    assert!(output.contains("#1 main variant 0 (line 8)"));
+    assert!(output.contains("value stack: [0, <function pick>, <function inc>]"));
+}
+
+#[test]
+fn line_breakpoint_stops_at_guard_once() {
+    let output = session("line", SOURCE, "break 5\ncontinue\neval x\ncontinue\n");
+    assert_eq!(
+        output
+            .matches("Breakpoint: pick variant 0 (line 5) guard")
+            .count(),
+        1
+    );
+    assert!(output.contains("(bmdb) 11\n"));
+    assert!(output.contains("Program finished: 01"));
+}
+
+#[test]
+fn finish_returns_to_the_caller() {
+    let output = session("finish", SOURCE, "break inc\ncontinue\nfinish\nfinish\n");
+    assert!(output.contains("Breakpoint: inc variant 2 (line 3)"));
+    assert!(output.contains("(bmdb) main variant 0 (line 8), at 9: call 1"));
+}
+
+#[test]
+fn stepi_executes_one_instruction() {
+    let output = session("stepi", SOURCE, "stepi\nstepi\n");
+    assert!(output.contains("main variant 0 (line 8), at 1: load_name \"pick\""));
+    assert!(output.contains("main variant 0 (line 8), at 2: trampoline"));
+}
+
+#[test]
+fn failed_match_keeps_the_failing_frame() {
+    let output = session(
+        "nomatch",
+        "f 0 = 1\nmain = f 1\n",
+        "continue\nbt\ncontinue\n",
+    );
+    assert!(output.contains("Stopped: No variant of function `f` matches"));
+    assert!(output.contains("#0 main variant 0 (line 2)"));
+    assert!(output.contains("The program has stopped: No variant of function `f` matches"));
+}
+
+#[test]
+fn runtime_errors_stop_the_program() {
+    let output = session(
+        "error",
+        "f x = x+missing\nmain = f 1\n",
+        "continue\neval x\n",
+    );
+    assert!(output.contains("Stopped: No such variable or function: `missing`"));
+    assert!(output.contains("f variant 0 (line 1), at 2: load_name \"missing\""));
+    assert!(output.contains("(bmdb) 1\n"));
+    assert!(!output.contains("Program finished"));
+}
+
+#[test]
+fn failed_arguments_report_their_error() {
+    let output = session(
+        "thunk",
+        "pragma lazy\nbad 0 = 1\nf x = x+x\nmain = f (bad 1)\n",
+        "continue\nframe 1\neval x\n",
+    );
+    assert!(output.contains("#1 f variant 0 (line 3)"));
+    assert!(output.contains(
+        "Error: Evaluation of the delayed argument failed: No variant of function `bad` matches"
+    ));
+}
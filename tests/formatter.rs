@@ -0,0 +1,120 @@
+use bitmachine::formatter::format;
+use std::path::Path;
+use std::process::Command;
+
+const MESSY: &str = "\
+# leading comment
+not   0 = 1
+inc  x  =  add x   1   # trailing
+# about not 1
+not 1=0
+
+
+main = (inc   0)+(not 1)+0x0f
+assert   inc 1   ==  10
+long_name ?a+x = x
+# end
+";
+
+#[test]
+fn formatting_is_idempotent() {
+    let formatted = format(MESSY).unwrap();
+    assert_eq!(format(&formatted).unwrap(), formatted);
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut sources = vec![root.join("src/prelude.bm")];
+    for entry in std::fs::read_dir(root.join("samples/tests")).unwrap() {
+        sources.push(entry.unwrap().path());
+    }
+    for path in sources {
+        let code = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(format(&code).unwrap(), code, "{}", path.display());
+    }
+}
+
+#[test]
+fn spacing_is_normalized() {
+    assert_eq!(
+        format("main   =  (inc   0)+(not  1)+0x0f\nassert   inc 1   ==  10\n").unwrap(),
+        "main = (inc 0)+(not 1)+0x0f\nassert inc 1 == 10\n"
+    );
+    assert_eq!(
+        format("f  x  =   x\n\n\n\ng =  1\n\n").unwrap(),
+        "f x = x\n\ng = 1\n"
+    );
+}
+
+#[test]
+fn variants_are_grouped_in_their_original_order() {
+    let source = "f 0 = 1\ng = 0\nf 1 = 0\nh = 1\nf x = x\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "f 0 = 1\nf 1 = 0\nf x = x\ng = 0\nh = 1\n"
+    );
+}
+
+#[test]
+fn equal_signs_of_a_group_are_aligned() {
+    let source = "and 1 1 = 1\nand ?a ?b = 0\nnot 0 = 1\nlong_name x = x\n";
+    assert_eq!(
+        format(source).unwrap(),
+        "and 1 1   = 1\nand ?a ?b = 0\nnot 0 = 1\nlong_name x = x\n"
+    );
+}
+
+#[test]
+fn comments_are_kept_with_their_variant() {
+    assert_eq!(
+        format(MESSY).unwrap(),
+        "\
+# leading comment
+not 0 = 1
+# about not 1
+not 1 = 0
+inc x = add x 1 # trailing
+
+main = (inc 0)+(not 1)+0x0f
+assert inc 1 == 10
+long_name ?a+x = x
+# end
+"
+    );
+}
+
+#[test]
+fn invalid_code_is_an_error() {
+    assert!(format("f x = (x\n").is_err());
+}
+
+#[test]
+fn check_exits_with_failure_if_files_would_change() {
+    let path = std::env::temp_dir().join("bitmachine-formatter-check.bm");
+    std::fs::write(&path, MESSY).unwrap();
+    let fmt = |check: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_bitmachine"));
+        command.arg("fmt");
+        if check {
+            command.arg("--check");
+        }
+        command.arg(&path).output().unwrap()
+    };
+
+    let output = fmt(true);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("Would reformat `{}`\n", path.display())
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), MESSY);
+
+    assert!(fmt(false).status.success());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format(MESSY).unwrap()
+    );
+
+    let output = fmt(true);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
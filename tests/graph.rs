@@ -0,0 +1,58 @@
+use std::process::Command;
+
+const SOURCE: &str = "\
+even .   = 1
+even x+b = odd x
+odd .    = 0
+odd x+b  = even x
+
+five = 101
+main = ?! (not (even five))
+";
+
+fn graph(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(format!("bitmachine-graph-{}.bm", name));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+        .arg("graph")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn nodes_show_variants_and_natives() {
+    let dot = graph("nodes", SOURCE);
+    assert!(dot.starts_with("digraph calls {\n"));
+    assert!(dot.contains("\"five\" [label=\"five\\n1 variant\"];"));
+    assert!(dot.contains("\"?!\" [label=\"?!\\nnative\", shape=box];"));
+    assert!(dot.contains("\"prelude.not\" [label=\"prelude.not\\n2 variants\", style=dashed];"));
+}
+
+#[test]
+fn edges_distinguish_tail_calls() {
+    let dot = graph("edges", SOURCE);
+    assert!(dot.contains("\"main\" -> \"?!\" [style=dashed, label=\"tail\"];"));
+    assert!(dot.contains("\"main\" -> \"prelude.not\";"));
+    assert!(dot.contains("\"main\" -> \"five\";"));
+}
+
+#[test]
+fn recursive_cycles_are_highlighted() {
+    let dot = graph("cycles", SOURCE);
+    assert!(dot.contains("\"even\" [label=\"even\\n2 variants\", color=red];"));
+    assert!(
+        dot.contains("\"odd\" -> \"even\" [style=dashed, label=\"tail\", color=red, penwidth=2];")
+    );
+    assert!(dot.contains("\"main\" -> \"even\";"));
+    assert!(dot.contains("\"main\" [label=\"main\\n1 variant\"];"));
+}
+
+#[test]
+fn locals_shadow_globals() {
+    let dot = graph("locals", "apply five x = five x\nfive = 101\n");
+    assert!(!dot.contains("->"));
+}
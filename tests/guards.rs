@@ -0,0 +1,74 @@
+use bitmachine::bindings::Bindings;
+use bitmachine::bitstring::BitString;
+use bitmachine::translator::Compile;
+use bitmachine::vm::{BasicExecResult, ExecError, VM};
+use bitmachine::{native_function, parser};
+
+const SOURCE: &str = "\
+kind x | long x = 11
+kind x          = 10
+sign ?s+x | s = 1
+sign x        = 0
+exact x | x = 1
+exact x     = 0
+only x | one x = x
+drop x | empty x = x
+drop ?a+x        = drop x
+long ?a+?b+?c+x = 1
+long x          = 0
+one 1 = 1
+one x = 0
+empty . = 1
+empty x = 0
+";
+
+fn run(name: &str, input: &str) -> BasicExecResult<BitString> {
+    let program = parser::parse(SOURCE).unwrap().compile();
+    let mut vm = VM::new(Bindings::from(program).union_with(native_function::make_bindings()));
+    vm.invoke_by_name(name, vec![bits(input).into()])?;
+    vm.run(Some(100_000)).map(|x| x.into_bit_string().unwrap())
+}
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+#[test]
+fn guards_choose_between_matching_variants() {
+    assert_eq!(run("kind", "1010").unwrap(), bits("11"));
+    assert_eq!(run("kind", "1").unwrap(), bits("10"));
+}
+
+#[test]
+fn guards_see_the_pattern_bindings() {
+    assert_eq!(run("sign", "100").unwrap(), bits("1"));
+    assert_eq!(run("sign", "011").unwrap(), bits("0"));
+}
+
+#[test]
+fn only_the_one_bit_string_1_selects_a_variant() {
+    assert_eq!(run("exact", "1").unwrap(), bits("1"));
+    for input in [".", "0", "01", "11"] {
+        assert_eq!(run("exact", input).unwrap(), bits("0"), "{}", input);
+    }
+}
+
+#[test]
+fn failing_every_guard_is_no_match() {
+    assert_eq!(run("only", "1").unwrap(), bits("1"));
+    assert!(matches!(run("only", "10"), Err(ExecError::NoMatch { .. })));
+}
+
+#[test]
+fn guarded_functions_recurse() {
+    assert_eq!(run("drop", "1010").unwrap(), bits("."));
+}
+
+#[test]
+fn guards_are_parsed_before_the_body() {
+    let program = parser::parse(SOURCE).unwrap();
+    let variants = &program.function_map["kind"].variants;
+    assert!(variants[0].guard.is_some());
+    assert!(variants[1].guard.is_none());
+    assert!(parser::parse("f x | = x\n").is_err());
+}
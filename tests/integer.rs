@@ -0,0 +1,204 @@
+use bitmachine::bitstring::BitString;
+use bitmachine::integer::{parse_argument, BitOrder, ByteOrder, ConversionError, Order};
+use std::process::Command;
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+fn to_text(s: &BitString) -> String {
+    s.iter().map(|bit| bit.to_string()).collect()
+}
+
+const ORDERS: [Order; 4] = [
+    Order {
+        bits: BitOrder::MsbFirst,
+        bytes: ByteOrder::BigEndian,
+    },
+    Order {
+        bits: BitOrder::MsbFirst,
+        bytes: ByteOrder::LittleEndian,
+    },
+    Order {
+        bits: BitOrder::LsbFirst,
+        bytes: ByteOrder::BigEndian,
+    },
+    Order {
+        bits: BitOrder::LsbFirst,
+        bytes: ByteOrder::LittleEndian,
+    },
+];
+
+#[test]
+fn round_trips_in_every_order() {
+    for order in ORDERS {
+        for value in [0i8, 1, -1, 127, -128] {
+            let s = BitString::from_int(value, 8, order).unwrap();
+            assert_eq!(s.to_int::<i8>(order), Ok(value));
+            assert_eq!(s.to_int::<u8>(order), Ok(value as u8));
+        }
+        for width in [64, 72, 128, 200] {
+            for value in [0i64, 1, -1, 77, -300, i64::MAX, i64::MIN] {
+                let s = BitString::from_int(value, width, order).unwrap();
+                assert_eq!(s.len(), width);
+                assert_eq!(s.to_int::<i64>(order), Ok(value));
+                assert_eq!(s.to_int::<i128>(order), Ok(value as i128));
+            }
+            for value in [0u64, 1, 200, u64::MAX] {
+                let s = BitString::from_int(value, width, order).unwrap();
+                assert_eq!(s.to_int::<u64>(order), Ok(value));
+                assert_eq!(s.to_int::<u128>(order), Ok(value as u128));
+            }
+        }
+    }
+}
+
+#[test]
+fn default_order_matches_literals() {
+    let order = Order::default();
+    assert_eq!(
+        to_text(&BitString::from_int(6u8, 5, order).unwrap()),
+        "00110"
+    );
+    assert_eq!(
+        to_text(&BitString::from_int(-3i32, 4, order).unwrap()),
+        "1101"
+    );
+    assert_eq!(bits("00110").to_int::<u8>(order), Ok(6));
+    assert_eq!(bits("1101").to_int::<i8>(order), Ok(-3));
+    assert_eq!(bits("1101").to_int::<u8>(order), Ok(13));
+    assert_eq!(BitString::empty().to_int::<u32>(order), Ok(0));
+}
+
+#[test]
+fn other_orders_reverse_bits_or_bytes() {
+    let value = 0x0102u16;
+    let layouts = [
+        "0000000100000010",
+        "0000001000000001",
+        "1000000001000000",
+        "0100000010000000",
+    ];
+    for (order, layout) in ORDERS.iter().zip(layouts) {
+        let s = BitString::from_int(value, 16, *order).unwrap();
+        assert_eq!(to_text(&s), layout);
+    }
+    // Reversing the whole string works for any width.
+    let s = BitString::from_int(6u8, 5, Order::lsb_first()).unwrap();
+    assert_eq!(to_text(&s), "01100");
+}
+
+#[test]
+fn wide_strings_are_sign_or_zero_extended() {
+    let s = BitString::from_int(-2i8, 300, Order::default()).unwrap();
+    assert_eq!(to_text(&s), format!("{}0", "1".repeat(299)));
+    assert_eq!(s.to_int::<i8>(Order::default()), Ok(-2));
+
+    let s = BitString::from_int(u128::MAX, 130, Order::default()).unwrap();
+    assert_eq!(to_text(&s), format!("00{}", "1".repeat(128)));
+    assert_eq!(s.to_int::<u128>(Order::default()), Ok(u128::MAX));
+}
+
+#[test]
+fn conversions_that_do_not_fit_fail() {
+    let order = Order::default();
+    assert_eq!(
+        BitString::from_int(256u16, 8, order),
+        Err(ConversionError::TooNarrow {
+            value: String::from("256"),
+            width: 8
+        })
+    );
+    assert!(BitString::from_int(8i8, 4, order).is_err());
+    assert!(BitString::from_int(-9i8, 4, order).is_err());
+    assert!(BitString::from_int(-8i8, 4, order).is_ok());
+    assert!(BitString::from_int(1u8, 0, order).is_err());
+
+    assert_eq!(
+        bits("100000000").to_int::<u8>(order),
+        Err(ConversionError::TooWide {
+            len: 9,
+            target: "u8"
+        })
+    );
+    // The sign bit of the string does not fit in the sign bit of `i8`.
+    assert!(bits("011111111").to_int::<i8>(order).is_err());
+    assert_eq!(bits("111111111").to_int::<i8>(order), Ok(-1));
+    assert!(bits("11111111").to_int::<i8>(order).is_ok());
+    assert!(bits(&"1".repeat(200)).to_int::<u128>(order).is_err());
+
+    let order = ORDERS[1];
+    assert_eq!(
+        BitString::from_int(5u8, 12, order),
+        Err(ConversionError::PartialBytes { len: 12 })
+    );
+    assert_eq!(
+        bits("101").to_int::<u8>(order),
+        Err(ConversionError::PartialBytes { len: 3 })
+    );
+}
+
+#[test]
+fn decimal_rendering() {
+    assert_eq!(BitString::empty().to_decimal(false), "0");
+    assert_eq!(BitString::empty().to_decimal(true), "0");
+    assert_eq!(bits("1101").to_decimal(false), "13");
+    assert_eq!(bits("1101").to_decimal(true), "-3");
+    assert_eq!(bits("1").to_decimal(true), "-1");
+    assert_eq!(bits("1000").to_decimal(true), "-8");
+
+    let max = BitString::from_int(u128::MAX, 128, Order::default()).unwrap();
+    assert_eq!(max.to_decimal(false), u128::MAX.to_string());
+    let min = BitString::from_int(i128::MIN, 128, Order::default()).unwrap();
+    assert_eq!(min.to_decimal(true), i128::MIN.to_string());
+
+    // 2^200 and -2^200, beyond any Rust integer.
+    let big = bits(&format!("1{}", "0".repeat(200)));
+    let expected = "1606938044258990275541962092341162602522202993782792835301376";
+    assert_eq!(big.to_decimal(false), expected);
+    assert_eq!(big.to_decimal(true), format!("-{}", expected));
+}
+
+#[test]
+fn arguments() {
+    assert_eq!(to_text(&parse_argument("1011").unwrap()), "1011");
+    assert_eq!(parse_argument(".").unwrap(), BitString::empty());
+    assert_eq!(to_text(&parse_argument("5u4").unwrap()), "0101");
+    assert_eq!(to_text(&parse_argument("-3i4").unwrap()), "1101");
+    assert_eq!(parse_argument("0u0").unwrap(), BitString::empty());
+    assert!(matches!(
+        parse_argument("16u4"),
+        Err(ConversionError::TooNarrow { .. })
+    ));
+    for invalid in ["", "12", "-3u4", "3u", "ui8", "2x"] {
+        assert_eq!(
+            parse_argument(invalid),
+            Err(ConversionError::InvalidArgument(String::from(invalid)))
+        );
+    }
+}
+
+#[test]
+fn run_passes_arguments_and_formats_the_result() {
+    let path = std::env::temp_dir().join("bitmachine-integer-run.bm");
+    std::fs::write(&path, "main x y = x+y\n").unwrap();
+    let run = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+            .arg("run")
+            .arg("--format")
+            .arg(format)
+            .arg(&path)
+            .arg("-3i4")
+            .arg("5u4")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        // Only the result is printed, without the trace.
+        assert!(output.stderr.is_empty());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run("bits"), "11010101\n");
+    assert_eq!(run("unsigned"), "213\n");
+    assert_eq!(run("signed"), "-43\n");
+    std::fs::remove_file(&path).unwrap();
+}
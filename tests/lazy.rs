@@ -0,0 +1,114 @@
+use bitmachine::ast::Evaluation;
+use bitmachine::bitstring::BitString;
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::vm::{BasicExecResult, ExecError, VM};
+use bitmachine::{make_global_bindings, parser, prelude};
+
+const SOURCE: &str = "\
+pragma memo fib
+
+fib x | lt x 10 = x
+fib x           = add (fib (dec x)) (fib (sub x 10))
+konst = 101
+twice f x = f (f x)
+wrap x = 1+(inner (bnot x))+0
+inner x = x+(rev 0 x)
+same x x = 1
+same x y = 0
+first ?a+x = a
+nested x = let y = (add x 1) in y+(first y)
+slow x = add x 1
+thrice x = x+x+x
+loop x = loop x
+";
+
+fn vm(source: &str, evaluation: Evaluation) -> VM {
+    let mut program = parser::parse(source).unwrap();
+    program.set_evaluation(evaluation);
+    VM::new(make_global_bindings(
+        program.compile(),
+        prelude::make_bindings(),
+    ))
+}
+
+/// Evaluates `expr` in a constant added to `source`.
+fn eval(source: &str, evaluation: Evaluation, expr: &str) -> BasicExecResult<Value> {
+    let source = format!("{}main = {}\n", source, expr);
+    let mut vm = vm(&source, evaluation);
+    vm.invoke_by_name("main", Vec::new()).unwrap();
+    vm.run(Some(100_000))
+}
+
+fn bits(text: &str) -> Value {
+    Value::from(text.parse::<BitString>().unwrap())
+}
+
+#[test]
+fn eager_and_lazy_calls_agree() {
+    for expr in [
+        "fib 1011",
+        "twice @inc konst",
+        "twice @not (and 1 (first konst))",
+        "wrap (bxor 111 konst)",
+        "(same (inc 1) 10)+(same (inc 1) 11)",
+        "nested (first 1101)",
+        "map 00 @bnot (add konst 11)",
+        "thrice (slow 10)",
+    ] {
+        let eager = eval(SOURCE, Evaluation::Eager, expr).unwrap();
+        let lazy = eval(SOURCE, Evaluation::Lazy, expr).unwrap();
+        assert_eq!(eager, lazy, "{}", expr);
+    }
+    assert_eq!(
+        eval(SOURCE, Evaluation::Lazy, "thrice (slow 10)").unwrap(),
+        bits("111111")
+    );
+}
+
+#[test]
+fn arguments_are_evaluated_at_most_once() {
+    let source = format!("{}main = thrice (slow 10)\n", SOURCE);
+    let mut vm = vm(&source, Evaluation::Lazy);
+    vm.enable_profiling();
+    vm.invoke_by_name("main", Vec::new()).unwrap();
+    assert_eq!(vm.run(None).unwrap(), bits("111111"));
+    assert_eq!(vm.profile().unwrap().functions()["slow"].calls, 1);
+}
+
+#[test]
+fn unneeded_arguments_are_not_evaluated() {
+    let source = format!("{}choose 1 t e = t\nchoose 0 t e = e\n", SOURCE);
+    let expr = "choose (same 1 1) 10 (loop 0)";
+    assert_eq!(eval(&source, Evaluation::Lazy, expr).unwrap(), bits("10"));
+    assert!(matches!(
+        eval(&source, Evaluation::Eager, expr),
+        Err(ExecError::StepLimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn results_are_evaluated() {
+    let source = format!("{}id x = x\n", SOURCE);
+    let result = eval(&source, Evaluation::Lazy, "id (slow 1)").unwrap();
+    assert!(matches!(result, Value::BitString(_)));
+    assert_eq!(result, bits("10"));
+}
+
+#[test]
+fn pragma_lazy_applies_to_the_file() {
+    let program = parser::parse("pragma lazy\nf x = x\n").unwrap();
+    assert_eq!(program.evaluation, Evaluation::Lazy);
+    assert_eq!(program.function_map["f"].evaluation, Evaluation::Lazy);
+
+    let error = parser::parse("pragma lazy f\nf = 1\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "`pragma lazy` takes no names at line 1, column 13"
+    );
+    let error = parser::parse("pragma memo\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "`pragma memo` needs function names at line 1, column 8"
+    );
+}
@@ -0,0 +1,58 @@
+use bitmachine::bindings::Bindings;
+use bitmachine::bitstring::BitString;
+use bitmachine::translator::Compile;
+use bitmachine::vm::{BasicExecResult, ExecError, VM};
+use bitmachine::{native_function, parser};
+
+const SOURCE: &str = "\
+swap ?a+?b = let ?x+?y = a+b in y+x
+shadow x = (let x = 0+x in x)+x
+nested x = (let x = 1 in (let x = 0 in x)+x)+x
+head x = let ?a+rest = x in a
+last . = .
+last ?a+x = let y = x in (first a y)
+first a . = a
+first a y = last y
+";
+
+fn run(name: &str, input: &str) -> BasicExecResult<BitString> {
+    let program = parser::parse(SOURCE).unwrap().compile();
+    let mut vm = VM::new(Bindings::from(program).union_with(native_function::make_bindings()));
+    vm.invoke_by_name(name, vec![bits(input).into()])?;
+    vm.run(Some(100_000)).map(|x| x.into_bit_string().unwrap())
+}
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+#[test]
+fn patterns_bind_parts_of_the_value() {
+    assert_eq!(run("swap", "10").unwrap(), bits("01"));
+    assert_eq!(run("head", "011").unwrap(), bits("0"));
+}
+
+#[test]
+fn bindings_shadow_until_the_body_ends() {
+    assert_eq!(run("shadow", "1").unwrap(), bits("011"));
+    assert_eq!(run("nested", "11").unwrap(), bits("0111"));
+}
+
+#[test]
+fn values_that_do_not_match_are_errors() {
+    assert!(matches!(
+        run("head", "."),
+        Err(ExecError::LetNoMatch { .. })
+    ));
+}
+
+#[test]
+fn recursion_goes_through_the_body() {
+    assert_eq!(run("last", &"1".repeat(100)).unwrap(), bits("1"));
+}
+
+#[test]
+fn let_is_a_keyword() {
+    assert!(parser::parse("f let = let\n").is_err());
+    assert!(parser::parse("f x = let y = x y\n").is_err());
+}
@@ -0,0 +1,61 @@
+use bitmachine::ast::Expr;
+use bitmachine::bitstring::BitString;
+use bitmachine::parser;
+
+fn literal(source: &str) -> BitString {
+    let program = parser::parse(&format!("main = {}\n", source)).unwrap();
+    match &program.function_map["main"].variants[0].body {
+        Expr::Literal(bits) => bits.clone(),
+        other => panic!("{:?} is not a literal", other),
+    }
+}
+
+fn error(source: &str) -> String {
+    let error = parser::parse(&format!("main = {}\n", source)).unwrap_err();
+    format!("{:#}", error)
+}
+
+#[test]
+fn radix_literals_keep_leading_zeros() {
+    assert_eq!(literal("0x0f").to_string(), "00001111");
+    assert_eq!(literal("0o17").to_string(), "001111");
+    assert_eq!(literal("0xfu12").to_string(), "000000001111");
+}
+
+#[test]
+fn decimal_literals_have_a_width() {
+    assert_eq!(literal("5u4").to_string(), "0101");
+    assert_eq!(literal("0u0").to_string(), ".");
+    assert_eq!(literal("18446744073709551616u65").len(), 65);
+}
+
+#[test]
+fn characters_and_strings_are_bytes() {
+    assert_eq!(literal("'a'").to_string(), "01100001");
+    assert_eq!(literal("\"hi\"").to_string(), "0110100001101001");
+    assert_eq!(literal("\"\"").to_string(), ".");
+}
+
+#[test]
+fn literals_that_do_not_fit_are_errors() {
+    assert_eq!(
+        error("5u2"),
+        "Invalid literal at line 1, column 8: Literal `5u2` does not fit in 2 bits"
+    );
+    assert_eq!(
+        error("0xffu4"),
+        "Invalid literal at line 1, column 8: Literal `0xffu4` does not fit in 4 bits"
+    );
+}
+
+#[test]
+fn widths_that_overflow_are_errors() {
+    assert_eq!(
+        error("5u99999999999999999999999"),
+        "Invalid literal at line 1, column 8: Width `99999999999999999999999` is too large"
+    );
+    assert_eq!(
+        error("0x1u99999999999999999999999"),
+        "Invalid literal at line 1, column 8: Width `99999999999999999999999` is too large"
+    );
+}
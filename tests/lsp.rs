@@ -0,0 +1,279 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+
+const URI: &str = "file:///tmp/sample.bm";
+
+const SOURCE: &str = "\
+inc x+0 = x+1
+inc x+1 = (inc x)+0
+inc .   = 1
+
+twice f x = f (f x)
+main = let y = inc 1 in twice inc y
+";
+
+/// Sends `requests` to a fresh `bitmachine lsp`, followed by `shutdown` and
+/// `exit`, and returns everything it wrote back.
+fn session(requests: Vec<Value>) -> Vec<Value> {
+    raw_session(requests.iter().map(|x| frame(&x.to_string())).collect())
+}
+
+fn frame(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+/// Same as `session`, with the requests already framed, so that they can be
+/// malformed.
+fn raw_session(requests: Vec<String>) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let initialize = json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {}});
+    let shutdown = json!({"jsonrpc": "2.0", "id": 999, "method": "shutdown"});
+    let exit = json!({"jsonrpc": "2.0", "method": "exit"});
+    let mut frames = vec![frame(&initialize.to_string())];
+    frames.extend(requests);
+    frames.push(frame(&shutdown.to_string()));
+    frames.push(frame(&exit.to_string()));
+    for frame in frames {
+        stdin.write_all(frame.as_bytes()).unwrap();
+    }
+    drop(stdin);
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut messages = Vec::new();
+    loop {
+        let mut header = String::new();
+        if stdout.read_line(&mut header).unwrap() == 0 {
+            break;
+        }
+        let length: usize = header
+            .trim_end()
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        stdout.read_line(&mut String::new()).unwrap();
+        let mut body = vec![0; length];
+        stdout.read_exact(&mut body).unwrap();
+        messages.push(serde_json::from_slice(&body).unwrap());
+    }
+    assert!(child.wait().unwrap().success());
+    messages
+}
+
+fn open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": URI, "languageId": "bitmachine", "version": 1, "text": text}},
+    })
+}
+
+fn request(id: u64, method: &str, line: u64, character: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": {"uri": URI},
+            "position": {"line": line, "character": character},
+        },
+    })
+}
+
+fn response(messages: &[Value], id: u64) -> &Value {
+    &messages.iter().find(|x| x["id"] == id).unwrap()["result"]
+}
+
+fn diagnostics(messages: &[Value]) -> Vec<&Value> {
+    messages
+        .iter()
+        .filter(|x| x["method"] == "textDocument/publishDiagnostics")
+        .collect()
+}
+
+#[test]
+fn initialize_reports_capabilities() {
+    let messages = session(Vec::new());
+    let capabilities = &response(&messages, 0)["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    assert_eq!(response(&messages, 999), &Value::Null);
+}
+
+#[test]
+fn valid_document_has_no_diagnostics() {
+    let messages = session(vec![open(SOURCE)]);
+    let published = diagnostics(&messages);
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    let messages = session(vec![open("inc x = x\nmain = (inc 1\n")]);
+    let published = diagnostics(&messages)[0]["params"]["diagnostics"].clone();
+    assert_eq!(published[0]["severity"], 1);
+    assert_eq!(published[0]["range"]["start"]["line"], 1);
+}
+
+#[test]
+fn static_checks_are_reported() {
+    let messages = session(vec![open("f x = g x\nf x y = x\n")]);
+    let published = diagnostics(&messages)[0]["params"]["diagnostics"].clone();
+    assert_eq!(published[0]["message"], "Unknown name `g`");
+    assert_eq!(
+        published[0]["range"],
+        json!({"start": {"line": 0, "character": 6}, "end": {"line": 0, "character": 7}})
+    );
+    assert_eq!(published[1]["severity"], 2);
+    assert_eq!(published[1]["range"]["start"]["line"], 1);
+}
+
+#[test]
+fn definition_lists_every_variant() {
+    let messages = session(vec![
+        open(SOURCE),
+        request(1, "textDocument/definition", 5, 33),
+    ]);
+    let locations = response(&messages, 1).as_array().unwrap();
+    let lines: Vec<_> = locations
+        .iter()
+        .map(|x| x["range"]["start"]["line"].clone())
+        .collect();
+    assert_eq!(lines, vec![json!(0), json!(1), json!(2)]);
+    assert_eq!(locations[0]["uri"], URI);
+}
+
+#[test]
+fn definition_of_a_local_is_empty() {
+    let messages = session(vec![
+        open(SOURCE),
+        request(1, "textDocument/definition", 4, 12),
+    ]);
+    assert_eq!(response(&messages, 1), &Value::Null);
+}
+
+#[test]
+fn hover_shows_variant_patterns() {
+    let messages = session(vec![
+        open(SOURCE),
+        request(1, "textDocument/hover", 1, 12),
+        request(2, "textDocument/hover", 0, 1),
+        request(3, "textDocument/hover", 4, 12),
+    ]);
+    let expected = "```\ninc x+0\ninc x+1\ninc .\n```";
+    assert_eq!(response(&messages, 1)["contents"]["value"], expected);
+    assert_eq!(response(&messages, 2)["contents"]["value"], expected);
+    assert_eq!(response(&messages, 3), &Value::Null);
+}
+
+#[test]
+fn hover_shows_prelude_functions() {
+    let messages = session(vec![
+        open("main = not 1\n"),
+        request(1, "textDocument/hover", 0, 8),
+    ]);
+    assert_eq!(
+        response(&messages, 1)["contents"]["value"],
+        "```\nnot 0\nnot 1\n```"
+    );
+}
+
+#[test]
+fn completion_offers_functions_and_locals() {
+    let messages = session(vec![
+        open(SOURCE),
+        request(1, "textDocument/completion", 5, 30),
+    ]);
+    let labels: Vec<_> = response(&messages, 1)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| (x["label"].as_str().unwrap().to_owned(), x["kind"].clone()))
+        .collect();
+    assert!(labels.contains(&(String::from("y"), json!(6))));
+    assert!(labels.contains(&(String::from("twice"), json!(3))));
+    assert!(labels.contains(&(String::from("add"), json!(3))));
+    assert!(!labels.iter().any(|(label, _)| label == "x"));
+}
+
+#[test]
+fn document_symbols_group_variants() {
+    let messages = session(vec![
+        open(SOURCE),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "textDocument/documentSymbol",
+            "params": {"textDocument": {"uri": URI}},
+        }),
+    ]);
+    let symbols = response(&messages, 1).as_array().unwrap();
+    let names: Vec<_> = symbols.iter().map(|x| x["name"].clone()).collect();
+    assert_eq!(names, vec![json!("inc"), json!("twice"), json!("main")]);
+    assert_eq!(symbols[0]["children"].as_array().unwrap().len(), 3);
+    assert_eq!(symbols[0]["range"]["end"]["line"], 2);
+}
+
+#[test]
+fn unknown_requests_get_an_error() {
+    let messages = session(vec![request(1, "textDocument/rename", 0, 0)]);
+    let reply = messages.iter().find(|x| x["id"] == 1).unwrap();
+    assert_eq!(reply["error"]["code"], -32601);
+}
+
+#[test]
+fn malformed_messages_are_answered_with_parse_errors() {
+    let messages = raw_session(vec![
+        frame("{\"jsonrpc\": \"2.0\", \"id\": 1,"),
+        String::from("Content-Length: many\r\n\r\n"),
+        frame(&open(SOURCE).to_string()),
+        frame(&request(2, "textDocument/definition", 5, 33).to_string()),
+    ]);
+    let errors: Vec<_> = messages
+        .iter()
+        .filter(|x| x["error"]["code"] == -32700)
+        .collect();
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|x| x["id"] == Value::Null));
+    assert!(errors[0]["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid JSON-RPC message"));
+    assert_eq!(
+        errors[1]["error"]["message"],
+        "Invalid Content-Length `many`"
+    );
+    assert_eq!(response(&messages, 2).as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn huge_content_lengths_are_not_allocated_up_front() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"Content-Length: 18446744073709551615\r\n\r\n{}")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("The input ended inside a message"));
+}
@@ -0,0 +1,117 @@
+use bitmachine::bitstring::BitString;
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::vm::VM;
+use bitmachine::{make_global_bindings, parser, prelude};
+
+const SOURCE: &str = "\
+pragma memo wrap count
+
+wrap x = 1+(inner x)+0
+inner x = x+x
+count .    = .
+count ?a+x = 1+(count x)
+noisy x = (?! x)+x
+
+first = 11+(wrap 10)
+second = (wrap 10)+0
+";
+
+fn vm(source: &str) -> VM {
+    let program = parser::parse(source).unwrap().compile();
+    let mut vm = VM::new(make_global_bindings(program, prelude::make_bindings()));
+    vm.enable_profiling();
+    vm
+}
+
+/// Runs `name` and returns its result and the instructions executed so far.
+fn run(vm: &mut VM, name: &str, arguments: &[&str]) -> (Value, u64) {
+    let arguments = arguments
+        .iter()
+        .map(|x| Value::from(x.parse::<BitString>().unwrap()))
+        .collect();
+    vm.invoke_by_name(name, arguments).unwrap();
+    let result = vm.run(None).unwrap();
+    let instructions = vm
+        .profile()
+        .unwrap()
+        .functions()
+        .values()
+        .map(|x| x.instructions)
+        .sum();
+    (result, instructions)
+}
+
+fn bits(text: &str) -> Value {
+    Value::from(text.parse::<BitString>().unwrap())
+}
+
+#[test]
+fn values_of_tail_calls_exclude_the_bits_of_callers() {
+    let mut vm = vm(SOURCE);
+    // `wrap` is itself tail-called with `11` before it, and tail-calls
+    // `inner` with bits on both sides.
+    let (result, before) = run(&mut vm, "first", &[]);
+    assert_eq!(result, bits("11110100"));
+    assert_eq!(vm.memo_len(), 1);
+
+    let (result, after) = run(&mut vm, "second", &[]);
+    assert_eq!(result, bits("1101000"));
+    assert_eq!(vm.memo_len(), 1);
+    // Those of `second` alone: load, load, call, load, concatenate.
+    assert_eq!(after - before, 5);
+}
+
+#[test]
+fn recursive_calls_are_recorded_once() {
+    let mut vm = vm(SOURCE);
+    let (result, first) = run(&mut vm, "count", &["10110"]);
+    assert_eq!(result, bits("11111"));
+    // One entry per suffix, including the empty one.
+    assert_eq!(vm.memo_len(), 6);
+
+    let (result, second) = run(&mut vm, "count", &["110110"]);
+    assert_eq!(result, bits("111111"));
+    assert_eq!(vm.memo_len(), 7);
+    assert!(second - first < first);
+}
+
+#[test]
+fn only_named_functions_are_memoized_unless_all_are() {
+    let mut vm = vm(SOURCE);
+    run(&mut vm, "inner", &["10"]);
+    assert_eq!(vm.memo_len(), 0);
+
+    vm.set_memoize_all(true);
+    let (result, _) = run(&mut vm, "inner", &["10"]);
+    assert_eq!(result, bits("1010"));
+    assert_eq!(vm.memo_len(), 1);
+}
+
+#[test]
+fn calls_of_impure_natives_are_not_recorded() {
+    let mut vm = vm(SOURCE);
+    vm.set_memoize_all(true);
+    let (result, _) = run(&mut vm, "noisy", &["1"]);
+    assert_eq!(result, bits("1"));
+    assert_eq!(vm.memo_len(), 0);
+
+    // The calls made before and after the impure one are still recorded.
+    run(&mut vm, "second", &[]);
+    assert_eq!(vm.memo_len(), 3);
+}
+
+#[test]
+fn pragmas_name_functions_of_the_file() {
+    let error = parser::parse("pragma memo f g\nf = 1\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "`g` is not defined in this file at line 1, column 15"
+    );
+    let error = parser::parse("pragma fast f\nf = 1\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "Unknown pragma `fast` at line 1, column 8"
+    );
+    assert!(parser::parse("pragma = 1\n").is_err());
+}
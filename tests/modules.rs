@@ -0,0 +1,182 @@
+use bitmachine::ast;
+use bitmachine::bindings::Bindings;
+use bitmachine::bitstring::BitString;
+use bitmachine::module::{ModuleLoader, SEARCH_PATH_VAR};
+use bitmachine::native_function;
+use bitmachine::translator::Compile;
+use bitmachine::vm::VM;
+use std::path::{Path, PathBuf};
+
+/// Writes `files` into a fresh directory named after the test.
+fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bitmachine-modules-{}", name));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    for (path, source) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+fn run(program: ast::Program) -> BitString {
+    let bindings = Bindings::from(program.compile()).union_with(native_function::make_bindings());
+    let mut vm = VM::new(bindings);
+    vm.invoke_by_name("main", Vec::new()).unwrap();
+    vm.run(Some(1000)).unwrap().into_bit_string().unwrap()
+}
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+fn error(loader: &mut ModuleLoader, path: &Path) -> String {
+    format!("{:#}", loader.load(path).unwrap_err())
+}
+
+#[test]
+fn imported_functions_are_qualified() {
+    let dir = tree(
+        "qualified",
+        &[
+            (
+                "main.bm",
+                "import \"lib.bm\"\nimport \"lib.bm\" as l\nmain = (lib.quad 1)+(l.twice 0)\n",
+            ),
+            (
+                "lib.bm",
+                "double x = x+x\nquad x = double (double x)\ntwice double = double+double\n",
+            ),
+        ],
+    );
+    let program = ModuleLoader::new(Vec::new())
+        .load(&dir.join("main.bm"))
+        .unwrap();
+    let mut names: Vec<_> = program.function_map.keys().cloned().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "l.double",
+            "l.quad",
+            "l.twice",
+            "lib.double",
+            "lib.quad",
+            "lib.twice",
+            "main"
+        ]
+    );
+    assert_eq!(run(program), bits("111100"));
+}
+
+#[test]
+fn namespaces_are_imported_once() {
+    let dir = tree(
+        "twice",
+        &[
+            (
+                "main.bm",
+                "import \"a/lib.bm\"\nimport \"b/lib.bm\"\nmain = 1\n",
+            ),
+            ("a/lib.bm", "f = 1\n"),
+            ("b/lib.bm", "f = 0\n"),
+        ],
+    );
+    let mut loader = ModuleLoader::new(Vec::new());
+    assert_eq!(
+        error(&mut loader, &dir.join("main.bm")),
+        format!(
+            "Namespace `lib` is imported more than once in `{}`",
+            dir.join("main.bm").display()
+        )
+    );
+}
+
+#[test]
+fn imports_are_searched_next_to_the_importer_first() {
+    let dir = tree(
+        "search",
+        &[
+            (
+                "src/main.bm",
+                "import \"util.bm\"\nimport \"extra.bm\"\nmain = util.f+extra.f\n",
+            ),
+            ("src/util.bm", "f = 1\n"),
+            ("lib/util.bm", "f = 0\n"),
+            ("lib/extra.bm", "f = 0\n"),
+        ],
+    );
+    let main = dir.join("src/main.bm");
+
+    let program = ModuleLoader::new(vec![dir.join("lib")])
+        .load(&main)
+        .unwrap();
+    assert_eq!(run(program), bits("10"));
+
+    let mut loader = ModuleLoader::new(Vec::new());
+    assert_eq!(
+        error(&mut loader, &main),
+        format!("Cannot find `extra.bm` imported from `{}`", main.display())
+    );
+}
+
+#[test]
+fn the_search_path_ends_with_the_environment() {
+    let dir = tree(
+        "env",
+        &[
+            ("main.bm", "import \"util.bm\"\nmain = util.f\n"),
+            ("first/util.bm", "f = 1\n"),
+            ("second/util.bm", "f = 0\n"),
+        ],
+    );
+    std::env::set_var(SEARCH_PATH_VAR, dir.join("second"));
+    let mut from_env = ModuleLoader::with_env_search_path(Vec::new());
+    let mut both = ModuleLoader::with_env_search_path(vec![dir.join("first")]);
+    std::env::remove_var(SEARCH_PATH_VAR);
+
+    assert_eq!(run(from_env.load(&dir.join("main.bm")).unwrap()), bits("0"));
+    assert_eq!(run(both.load(&dir.join("main.bm")).unwrap()), bits("1"));
+}
+
+#[test]
+fn import_cycles_are_errors() {
+    let dir = tree(
+        "cycle",
+        &[
+            ("main.bm", "import \"a.bm\"\nmain = a.f\n"),
+            ("a.bm", "import \"b.bm\"\nf = b.f\n"),
+            ("b.bm", "import \"a.bm\"\nf = 1\n"),
+        ],
+    );
+    let canonical = |name: &str| dir.join(name).canonicalize().unwrap();
+    let mut loader = ModuleLoader::new(Vec::new());
+    let message = error(&mut loader, &dir.join("main.bm"));
+    assert!(
+        message.ends_with(&format!(
+            "Import cycle: {} -> {} -> {}",
+            canonical("a.bm").display(),
+            canonical("b.bm").display(),
+            canonical("a.bm").display()
+        )),
+        "{}",
+        message
+    );
+
+    // The loader can be used again after an error.
+    let dir = tree(
+        "diamond",
+        &[
+            (
+                "main.bm",
+                "import \"a.bm\"\nimport \"b.bm\"\nmain = a.f+b.f\n",
+            ),
+            ("a.bm", "import \"c.bm\"\nf = c.f\n"),
+            ("b.bm", "import \"c.bm\"\nf = c.f\n"),
+            ("c.bm", "f = 1\n"),
+        ],
+    );
+    assert_eq!(run(loader.load(&dir.join("main.bm")).unwrap()), bits("11"));
+}
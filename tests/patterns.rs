@@ -0,0 +1,105 @@
+use bitmachine::bitstring::BitString;
+use bitmachine::parser;
+use bitmachine::pattern::PatternParseMulti;
+use bitmachine::value::Value;
+
+fn text(value: Value) -> String {
+    let bits = value.into_bit_string().unwrap();
+    if bits.is_empty() {
+        return String::from(".");
+    }
+    bits.iter().map(|bit| bit.to_string()).collect()
+}
+
+/// Matches `args` against the patterns of `f <patterns> = 1` and lists the
+/// bindings by name, as `name=bits`.
+fn bind(patterns: &str, args: &[&str]) -> Option<Vec<String>> {
+    let program = parser::parse(&format!("f {} = 1\n", patterns)).unwrap();
+    let patterns = &program.function_map["f"].variants[0].patterns;
+    let args = args
+        .iter()
+        .map(|x| Value::from(x.parse::<BitString>().unwrap()))
+        .collect();
+    let mut bindings: Vec<_> = patterns
+        .parse(args)?
+        .into_map()
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, text(value)))
+        .collect();
+    bindings.sort();
+    Some(bindings)
+}
+
+#[test]
+fn repeated_variables_bind_equal_values() {
+    assert_eq!(bind("x x", &["101", "101"]).unwrap(), ["x=101"]);
+    assert_eq!(bind("x x", &["101", "100"]), None);
+    assert_eq!(bind("x x", &["101", "0101"]), None);
+    assert_eq!(bind("?a+?a", &["11"]).unwrap(), ["a=1"]);
+    assert_eq!(bind("?a+?a", &["10"]), None);
+    assert_eq!(bind("?a+x ?a", &["10", "1"]).unwrap(), ["a=1", "x=0"]);
+    assert_eq!(bind("?a+x ?a", &["10", "0"]), None);
+}
+
+#[test]
+fn variable_segments_are_matched_leftmost_shortest() {
+    assert_eq!(bind("a+01+b", &["0101"]).unwrap(), ["a=.", "b=01"]);
+    assert_eq!(bind("a+1+b+0", &["110"]).unwrap(), ["a=.", "b=1"]);
+    assert_eq!(
+        bind("a+0+b+0+c", &["1101100"]).unwrap(),
+        ["a=11", "b=11", "c=0"]
+    );
+}
+
+#[test]
+fn matching_backtracks_into_earlier_segments() {
+    assert_eq!(bind("a+11+b", &["10110"]).unwrap(), ["a=10", "b=0"]);
+    assert_eq!(bind("a+1+a", &["01101"]).unwrap(), ["a=01"]);
+    assert_eq!(bind("a+11+b", &["1010"]), None);
+    assert_eq!(bind("a+1+b+1", &["0000"]), None);
+}
+
+#[test]
+fn repeated_variable_length_segments_are_compared() {
+    assert_eq!(bind("a+a", &["101101"]).unwrap(), ["a=101"]);
+    assert_eq!(bind("a+a", &["1011"]), None);
+    assert_eq!(bind("a+a", &["."]).unwrap(), ["a=."]);
+    assert_eq!(bind("a+0+a", &["11011"]).unwrap(), ["a=11"]);
+}
+
+#[test]
+fn fields_and_ranges_bind_that_many_bits() {
+    assert_eq!(bind("x:3+y", &["10110"]).unwrap(), ["x=101", "y=10"]);
+    assert_eq!(bind("x:3", &["1011"]), None);
+    assert_eq!(bind("x:{2..3}+y", &["1"]), None);
+    assert_eq!(bind("x:{2..3}+0+y", &["11101"]).unwrap(), ["x=111", "y=1"]);
+    assert_eq!(bind("x:{..1}+y:{2..}", &["101"]).unwrap(), ["x=.", "y=101"]);
+    assert_eq!(bind("x:{..1}+y:{2..2}", &["101"]).unwrap(), ["x=1", "y=01"]);
+    assert_eq!(bind("x:{2..2}", &["10"]).unwrap(), ["x=10"]);
+}
+
+#[test]
+fn lengths_that_overflow_are_errors() {
+    let error = |source| format!("{:#}", parser::parse(source).unwrap_err());
+    assert_eq!(
+        error("f x:99999999999999999999999 = x\n"),
+        "Length `99999999999999999999999` is too large at line 1, column 5"
+    );
+    assert_eq!(
+        error("f x:{99999999999999999999999..} = x\n"),
+        "Length `99999999999999999999999` is too large at line 1, column 6"
+    );
+    assert_eq!(
+        error("f x:{..99999999999999999999999}+1 = x\n"),
+        "Length `99999999999999999999999` is too large at line 1, column 8"
+    );
+}
+
+#[test]
+fn empty_ranges_are_errors() {
+    let error = parser::parse("f x:{16..4} = x\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "Range of `x` matches no length at line 1, column 3"
+    );
+}
@@ -0,0 +1,60 @@
+use bitmachine::bindings::Bindings;
+use bitmachine::bitstring::BitString;
+use bitmachine::translator::Compile;
+use bitmachine::vm::{BasicExecResult, ExecError, VM};
+use bitmachine::{global_names, native_function, parser, prelude};
+
+fn run(source: &str, prelude: Bindings) -> BasicExecResult<BitString> {
+    let program = parser::parse(source).unwrap().compile();
+    let mut vm = VM::new(
+        prelude
+            .union_with(program.into())
+            .union_with(native_function::make_bindings()),
+    );
+    vm.invoke_by_name("main", Vec::new())?;
+    vm.run(Some(100_000)).map(|x| x.into_bit_string().unwrap())
+}
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+#[test]
+fn prelude_functions_are_available_by_both_names() {
+    let source = "main = (inc 0111)+(prelude.inc 0111)\n";
+    assert_eq!(
+        run(source, prelude::make_bindings()).unwrap(),
+        bits("10001000")
+    );
+}
+
+#[test]
+fn program_definitions_shadow_the_prelude() {
+    let source = "not x = x\nmain = (not 1)+(prelude.not 1)\n";
+    assert_eq!(run(source, prelude::make_bindings()).unwrap(), bits("10"));
+}
+
+#[test]
+fn shadowing_does_not_change_other_prelude_functions() {
+    // `bnot` calls the prelude `not`, not the one defined here.
+    let source = "not x = x\nmain = (bnot 10)+(not 10)\n";
+    assert_eq!(run(source, prelude::make_bindings()).unwrap(), bits("0110"));
+}
+
+#[test]
+fn the_prelude_can_be_left_out() {
+    assert!(matches!(
+        run("main = not 1\n", Bindings::empty()),
+        Err(ExecError::VariableNotFound { .. })
+    ));
+}
+
+#[test]
+fn global_names_are_the_natives_and_the_prelude() {
+    let names = global_names(prelude::make_bindings());
+    assert!(names.contains("$"));
+    assert!(names.contains("inc"));
+    assert!(names.contains("prelude.inc"));
+    assert!(!names.contains("test"));
+    assert!(!global_names(Bindings::empty()).contains("inc"));
+}
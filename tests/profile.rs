@@ -0,0 +1,70 @@
+use std::process::Command;
+
+const SOURCE: &str = "\
+inc x+0 = x+1
+inc x+1 = (inc x)+0
+inc .   = 1
+
+main = 0+(inc (inc 1))
+";
+
+/// Runs `bitmachine run --profile` on `source` and returns the report and the
+/// collapsed stacks.
+fn profile(name: &str, source: &str) -> (String, String) {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("bitmachine-profile-{}.bm", name));
+    let stacks = dir.join(format!("bitmachine-profile-{}.folded", name));
+    std::fs::write(&path, source).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+        .arg("run")
+        .arg("--profile")
+        .arg("--stacks")
+        .arg(&stacks)
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let folded = std::fs::read_to_string(&stacks).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&stacks).unwrap();
+    (String::from_utf8(output.stderr).unwrap(), folded)
+}
+
+/// Columns of the report row starting with `label`.
+fn row<'a>(report: &'a str, label: &str) -> Vec<&'a str> {
+    let line = report.lines().find(|line| line.starts_with(label)).unwrap();
+    line[label.len()..].split_whitespace().collect()
+}
+
+#[test]
+fn counts_calls_and_matches() {
+    let (report, _) = profile("counts", SOURCE);
+    // inc 1 -> (inc .)+0 -> 1+0, then 0+(inc 10) -> 0+11.
+    assert_eq!(row(&report, "inc "), vec!["3", "2", "11", "6", "3", "7"]);
+    assert_eq!(row(&report, "  variant 0 (line 1)"), vec!["1", "3", "2"]);
+    assert_eq!(row(&report, "  variant 1 (line 2)"), vec!["1", "2", "1"]);
+    assert_eq!(row(&report, "  variant 2 (line 3)"), vec!["1", "1", "0"]);
+    assert!(report.contains("19 instructions, 4 calls (2 regular, 2 tail), 3 match failures"));
+}
+
+#[test]
+fn functions_are_sorted_by_instructions() {
+    let (report, _) = profile("sorted", SOURCE);
+    let functions: Vec<_> = report
+        .lines()
+        .skip_while(|line| !line.starts_with("function "))
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter(|line| !line.starts_with(' '))
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+    assert_eq!(functions, vec!["inc", "main"]);
+}
+
+#[test]
+fn writes_collapsed_stacks() {
+    let (_, folded) = profile("stacks", SOURCE);
+    // The outer call of `inc` is a tail call, which replaces `main`.
+    assert_eq!(folded, "inc 4\nmain 8\nmain;inc 7\n");
+}
@@ -0,0 +1,124 @@
+use bitmachine::bitstring::{Bit, BitString};
+use bitmachine::property::{bindings_from_source, Counterexample, Property, PropertyError};
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+
+const SOURCE: &str = "\
+id x = x
+partial 1 = 1
+";
+
+fn reversed(bits: &BitString) -> BitString {
+    let bits: Vec<_> = bits.iter().collect();
+    bits.into_iter().rev().collect()
+}
+
+fn counterexample(result: Result<(), PropertyError>) -> Counterexample {
+    match result {
+        Err(PropertyError::Counterexample(counterexample)) => counterexample,
+        other => panic!("{:?} is not a counterexample", other),
+    }
+}
+
+#[test]
+fn inputs_are_drawn_from_the_width_ranges() {
+    let bindings = bindings_from_source(SOURCE).unwrap();
+    let generate = |seed| {
+        let inputs = RefCell::new(Vec::new());
+        Property::new("id")
+            .arg(3..=5)
+            .cases(200)
+            .seed(seed)
+            .check(&bindings, |args| {
+                inputs.borrow_mut().push(args[0].clone());
+                args[0].clone()
+            })
+            .unwrap();
+        inputs.into_inner()
+    };
+
+    let inputs = generate(1);
+    assert_eq!(inputs.len(), 200);
+    for width in 3..=5 {
+        assert!(inputs.iter().any(|x| x.len() == width));
+    }
+    assert!(inputs.iter().all(|x| (3..=5).contains(&x.len())));
+    assert!(inputs.iter().any(|x| x.iter().any(|bit| bit == Bit::One)));
+    assert_eq!(generate(1), inputs);
+    assert_ne!(generate(2), inputs);
+}
+
+#[test]
+fn failing_inputs_are_shrunk() {
+    let bindings = bindings_from_source(SOURCE).unwrap();
+    for seed in 0..10 {
+        let failure = counterexample(
+            Property::new("id")
+                .arg(1..=16)
+                .seed(seed)
+                .check(&bindings, |args| reversed(&args[0])),
+        );
+        // The smallest inputs that are not palindromes are `01` and `10`.
+        let argument = &failure.arguments[0];
+        assert_eq!(argument.len(), 2, "{}", failure);
+        assert_eq!(argument.iter().filter(|x| *x == Bit::One).count(), 1);
+        assert_eq!(failure.expected, reversed(argument));
+        assert_eq!(failure.seed, seed);
+    }
+}
+
+#[test]
+fn shrinking_stays_within_the_width_range() {
+    let bindings = bindings_from_source(SOURCE).unwrap();
+    let failure = counterexample(
+        Property::new("id")
+            .arg(4..=8)
+            .check(&bindings, |args| !args[0].clone()),
+    );
+    assert_eq!(failure.arguments[0].to_string(), "0000");
+    assert_eq!(failure.expected.to_string(), "1111");
+}
+
+#[test]
+fn errors_are_failures() {
+    let bindings = bindings_from_source(SOURCE).unwrap();
+    let failure = counterexample(
+        Property::new("partial")
+            .arg(1..=1)
+            .check(&bindings, |_| "1".parse().unwrap()),
+    );
+    assert_eq!(failure.arguments[0].to_string(), "0");
+    let error = failure.actual.unwrap_err();
+    assert!(
+        error.starts_with("No variant of function `partial`"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn invalid_properties_are_errors() {
+    let bindings = bindings_from_source(SOURCE).unwrap();
+    let check = |property: Property| {
+        property
+            .check(&bindings, |args| args[0].clone())
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        check(
+            Property::new("id")
+                .arg(1..=2)
+                .arg(RangeInclusive::new(5, 4))
+        ),
+        "Width range 5..=4 of argument 1 is empty"
+    );
+    assert_eq!(
+        check(Property::new("id").arg(1..=2).arg(1..=2)),
+        "No variant of `id` takes 2 arguments"
+    );
+    assert_eq!(
+        check(Property::new("missing").arg(1..=2)),
+        "No such function: `missing`"
+    );
+}
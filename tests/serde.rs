@@ -0,0 +1,109 @@
+#![cfg(feature = "serde")]
+
+use bitmachine::bitstring::BitString;
+use bitmachine::callable::Callable;
+use bitmachine::compiled::Program;
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::vm::VM;
+use bitmachine::{make_global_bindings, native_function, parser, prelude};
+
+const SOURCE: &str = "\
+inc x+0 = x+1
+inc x+1 = (inc x)+0
+inc .   = 1
+
+twice f x = f (f x)
+main = 0+(twice inc 1011)+(?! 1)
+
+assert twice inc 11 == 101
+";
+
+fn bits(text: &str) -> BitString {
+    text.parse().unwrap()
+}
+
+fn compile(source: &str) -> Program {
+    parser::parse(source).unwrap().compile()
+}
+
+fn run(program: Program) -> Value {
+    let mut vm = VM::new(make_global_bindings(program, prelude::make_bindings()));
+    vm.invoke_by_name("main", vec![]).unwrap();
+    vm.run(None).unwrap()
+}
+
+#[test]
+fn bit_strings_are_text_in_json() {
+    for text in [".", "0", "1011", "10110011101"] {
+        let json = serde_json::to_string(&bits(text)).unwrap();
+        assert_eq!(json, format!("\"{}\"", text));
+        assert_eq!(
+            serde_json::from_str::<BitString>(&json).unwrap(),
+            bits(text)
+        );
+    }
+    assert!(serde_json::from_str::<BitString>("\"102\"").is_err());
+    assert!(serde_json::from_str::<BitString>("\"\"")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn bit_strings_are_bytes_in_binary_formats() {
+    let s = bits("1011001110");
+    let slice = s.slice(3, 10);
+    for value in [s.clone(), slice, BitString::empty()] {
+        let encoded = bincode::serialize(&value).unwrap();
+        assert_eq!(bincode::deserialize::<BitString>(&encoded).unwrap(), value);
+    }
+    let encoded = bincode::serialize(&s).unwrap();
+    // The length, the number of bytes, then the bytes.
+    assert_eq!(encoded.len(), 8 + 8 + 2);
+
+    let mut truncated = bincode::serialize(&(10usize, vec![0u8])).unwrap();
+    assert!(bincode::deserialize::<BitString>(&truncated).is_err());
+    truncated = bincode::serialize(&(16usize, vec![0u8, 255])).unwrap();
+    assert_eq!(
+        bincode::deserialize::<BitString>(&truncated).unwrap(),
+        bits("0000000011111111")
+    );
+}
+
+#[test]
+fn values_round_trip() {
+    let natives = native_function::make_bindings();
+    let debug = natives.get_value("?!").unwrap().clone();
+    let values = vec![Value::from(bits("0110")), debug];
+    let json = serde_json::to_string(&values).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"BitString":"0110"},{"Callable":{"Native":"?!"}}]"#
+    );
+    let read: Vec<Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, values);
+
+    let error = serde_json::from_str::<Value>(r#"{"Callable":{"Native":"nope"}}"#).unwrap_err();
+    assert!(error.to_string().contains("Unknown native function `nope`"));
+}
+
+#[test]
+fn compiled_programs_round_trip() {
+    let program = compile(SOURCE);
+    let expected = run(program.clone());
+    assert_eq!(expected, Value::from(bits("01101")));
+
+    let json = serde_json::to_string(&program).unwrap();
+    let from_json: Program = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_json.tests.len(), 1);
+    assert_eq!(from_json.tests[0].line, 8);
+    assert_eq!(run(from_json), expected);
+
+    let encoded = bincode::serialize(&program).unwrap();
+    let from_binary: Program = bincode::deserialize(&encoded).unwrap();
+    match &from_binary.function_map["inc"] {
+        Callable::Coded(function) => assert_eq!(function.variants.len(), 3),
+        Callable::Native(_) => panic!("`inc` is not native"),
+    }
+    assert_eq!(run(from_binary), expected);
+}
@@ -0,0 +1,57 @@
+use bitmachine::bitstring::{Bit, BitString};
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::vm::{ExecError, VM};
+use bitmachine::{make_global_bindings, parser, prelude};
+
+const SOURCE: &str = "\
+flip . = .
+flip ?a+x = (not a)+(flip x)
+pairs . = .
+pairs ?a+x = 1+(not a)+a+(and a a)+(pairs x)+0
+";
+
+/// Runs `name` on `input` and returns its result and the deepest the task
+/// stack got.
+fn run(name: &str, input: &BitString) -> (Value, usize) {
+    let program = parser::parse(SOURCE).unwrap().compile();
+    let mut vm = VM::new(make_global_bindings(program, prelude::make_bindings()));
+    vm.invoke_by_name(name, vec![input.clone().into()]).unwrap();
+
+    let mut depth = 0;
+    loop {
+        depth = depth.max(vm.depth());
+        match vm.step() {
+            Err(ExecError::TaskStackEmpty) => break,
+            x => {
+                x.unwrap();
+            }
+        }
+    }
+    (vm.result().cloned().unwrap(), depth)
+}
+
+fn ones(n: usize) -> BitString {
+    std::iter::repeat_n(Bit::One, n).collect()
+}
+
+#[test]
+fn only_the_last_call_of_a_concatenation_is_a_tail_call() {
+    let (result, short) = run("flip", &ones(10));
+    assert_eq!(result, Value::from(!ones(10)));
+    let (result, long) = run("flip", &ones(1000));
+    assert_eq!(result, Value::from(!ones(1000)));
+    assert_eq!(short, long);
+    assert!(long <= 2);
+}
+
+#[test]
+fn earlier_calls_are_prepended_in_order() {
+    let input: BitString = "10".parse().unwrap();
+    let (result, _) = run("pairs", &input);
+    assert_eq!(result.to_string(), "1011110000");
+
+    let (_, short) = run("pairs", &ones(10));
+    let (_, long) = run("pairs", &ones(1000));
+    assert_eq!(short, long);
+}
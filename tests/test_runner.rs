@@ -0,0 +1,122 @@
+use bitmachine::module::ModuleLoader;
+use bitmachine::prelude;
+use bitmachine::test_runner::{self, TestOptions};
+use serde_json::{json, Value as Json};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const PASSING: &str = "\
+test \"inc\" = inc 1 == 10
+assert not 0 == 1
+";
+
+const FAILING: &str = "\
+test \"wrong\" = inc 1 == 11
+test \"nul\\0<&>\" = loop 1
+assert 1 == 1
+loop x = loop x
+";
+
+fn write(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bitmachine-test-runner-{}", name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn run_tests(paths: &[PathBuf], options: &TestOptions) -> bool {
+    let mut loader = ModuleLoader::new(Vec::new());
+    test_runner::run_tests(paths, &mut loader, prelude::make_bindings(), options).unwrap()
+}
+
+#[test]
+fn samples_pass() {
+    let samples = [Path::new(env!("CARGO_MANIFEST_DIR")).join("samples/tests")];
+    assert!(run_tests(&samples, &TestOptions::default()));
+    let lazy = TestOptions {
+        lazy: true,
+        ..TestOptions::default()
+    };
+    assert!(run_tests(&samples, &lazy));
+}
+
+#[test]
+fn json_report_lists_every_test() {
+    let passing = write("json-passing.bm", PASSING);
+    let failing = write("json-failing.bm", FAILING);
+    let report = write("report.json", "");
+    let options = TestOptions {
+        step_limit: 1000,
+        json_report: Some(report.clone()),
+        ..TestOptions::default()
+    };
+    assert!(!run_tests(&[passing.clone(), failing.clone()], &options));
+
+    let mut json: Json = serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    for test in json["tests"].as_array_mut().unwrap() {
+        assert!(test["duration_ms"].is_u64());
+        test.as_object_mut().unwrap().remove("duration_ms");
+    }
+    let (passing, failing) = (passing.display().to_string(), failing.display().to_string());
+    assert_eq!(
+        json,
+        json!({
+            "passed": 3,
+            "failed": 2,
+            "tests": [
+                {"file": passing, "name": "inc", "line": 1, "status": "passed"},
+                {"file": passing, "name": "not 0 == 1", "line": 2, "status": "passed"},
+                {
+                    "file": failing,
+                    "name": "wrong",
+                    "line": 1,
+                    "status": "failed",
+                    "expected": "11",
+                    "actual": "10"
+                },
+                {
+                    "file": failing,
+                    "name": "nul\u{0}<&>",
+                    "line": 2,
+                    "status": "error",
+                    "error": "Execution did not finish within 1000 steps"
+                },
+                {"file": failing, "name": "1 == 1", "line": 3, "status": "passed"},
+            ]
+        })
+    );
+}
+
+#[test]
+fn junit_report_is_valid_xml() {
+    let failing = write("junit-failing.bm", FAILING);
+    let report = write("report.xml", "");
+    let options = TestOptions {
+        step_limit: 1000,
+        junit_report: Some(report.clone()),
+        ..TestOptions::default()
+    };
+    assert!(!run_tests(&[failing], &options));
+
+    let xml = std::fs::read_to_string(&report).unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+    assert!(xml.contains("<testsuite name=\"bitmachine\" tests=\"3\" failures=\"1\" errors=\"1\">"));
+    assert!(xml.contains("name=\"wrong\""));
+    assert!(xml.contains("<failure message=\"expected 11, got 10\"/>"));
+    assert!(xml.contains("name=\"nul\u{fffd}&lt;&amp;&gt;\""));
+    assert!(xml.contains("<error message=\"Execution did not finish within 1000 steps\"/>"));
+    assert!(!xml.chars().any(|c| c < ' ' && c != '\n'));
+}
+
+#[test]
+fn exit_status_reflects_failures() {
+    let test = |path: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+            .args(["test", "--step-limit", "1000"])
+            .arg(path)
+            .output()
+            .unwrap()
+            .status
+    };
+    assert!(test(&write("exit-passing.bm", PASSING)).success());
+    assert_eq!(test(&write("exit-failing.bm", FAILING)).code(), Some(1));
+}
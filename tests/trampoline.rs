@@ -0,0 +1,104 @@
+use bitmachine::bindings::Bindings;
+use bitmachine::bitstring::BitString;
+use bitmachine::callable::Callable;
+use bitmachine::check::{self, Severity};
+use bitmachine::translator::Compile;
+use bitmachine::value::Value;
+use bitmachine::vm::VM;
+use bitmachine::{formatter, make_global_bindings, parser};
+use std::collections::HashSet;
+use std::process::Command;
+
+fn warnings(source: &str) -> Vec<String> {
+    let program = parser::parse(source).unwrap();
+    check::check(&program, &HashSet::new())
+        .into_iter()
+        .filter(|x| x.severity == Severity::Warning)
+        .map(|x| x.message)
+        .collect()
+}
+
+fn run(source: &str, name: &str) -> Value {
+    let program = parser::parse(source).unwrap().compile();
+    let mut vm = VM::new(make_global_bindings(program, Bindings::empty()));
+    vm.invoke_by_name(name, Vec::new()).unwrap();
+    vm.run(Some(1000)).unwrap()
+}
+
+fn bits(text: &str) -> Value {
+    Value::from(text.parse::<BitString>().unwrap())
+}
+
+#[test]
+fn trampolining_repeats_until_the_value_is_bits() {
+    let source = "a = @b\nb = @c\nc = 1\nmain = a+a\n";
+    assert_eq!(run(source, "main"), bits("11"));
+    let called = run("a = @b\nb = @c\nc = 1\nmain = !a\n", "main");
+    assert_eq!(called.into_callable().unwrap().name(), "b");
+}
+
+#[test]
+fn function_values_are_not_called_where_named() {
+    let source = "@f = 1\nf x = x+x\ng = f\nh = !f\n";
+    let program = parser::parse(source).unwrap();
+    assert!(program.function_map["f"].function_value);
+    let compiled = program.compile();
+    match &compiled.function_map["f"] {
+        Callable::Coded(f) => assert!(f.function_value && !f.is_trampoline_callable()),
+        Callable::Native(_) => unreachable!(),
+    }
+
+    assert_eq!(run(source, "g").into_callable().unwrap().name(), "f");
+    assert_eq!(run(source, "h"), bits("1"));
+}
+
+#[test]
+fn constants_with_other_variants_are_reported() {
+    let messages = warnings("f = 1\nf x = x\nf x y = x\n@g = 1\ng x = x\nh = 1\n");
+    assert_eq!(
+        messages,
+        [
+            "`f` is called without arguments wherever it is named, so its other variants are \
+             only reachable through `@f`; define this one as `@f = ...` to make `f` a function \
+             value",
+            "`f` takes 2 arguments here but 1 in its first variant",
+        ]
+    );
+}
+
+#[test]
+fn run_and_test_print_the_warnings() {
+    let path = std::env::temp_dir().join("bitmachine-trampoline-warnings.bm");
+    std::fs::write(&path, "f = 1\nf x = x\nmain = f\nassert f == 1\n").unwrap();
+    let warning = format!(
+        "Warning in `{}`: `f` is called without arguments wherever it is named, so its other \
+         variants are only reachable through `@f`; define this one as `@f = ...` to make `f` a \
+         function value at line 1, column 1\n",
+        path.display()
+    );
+    for command in ["run", "test"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_bitmachine"))
+            .arg(command)
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), warning);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn only_definitions_without_patterns_are_marked() {
+    let error = parser::parse("@f x = x\n").unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "Only a definition without patterns can be marked with `@` at line 1, column 1"
+    );
+}
+
+#[test]
+fn markers_are_formatted() {
+    let source = "@f   =  1\nf x = !f+x\n";
+    assert_eq!(formatter::format(source).unwrap(), "@f  = 1\nf x = !f+x\n");
+}